@@ -1,69 +1,39 @@
-use chrono::prelude::*;
-use futures_util::{SinkExt, StreamExt};
 use kraken::{
     core::CurrencyPair,
-    message::{publication::Trade, request::subscribe},
+    message::{channel::ChannelName, request::subscribe},
+    ws::{self, Event},
+    Error,
 };
-use tokio_tungstenite::tungstenite::Message;
 
 #[tokio::main]
-async fn main() {
-    /*let res = reqwest::get("https://api.kraken.com/0/private/GetWebSocketsToken")
-            .await
-            .unwrap()
-            .text()
-            .await
-            .unwrap();
-    */
+async fn main() -> Result<(), Error> {
+    let mut client = ws::Client::connect(ws::Config::default());
 
-    //println!("{:#?}", res);
-
-    let resp = reqwest::get("https://api.kraken.com/0/public/AssetPairs")
-        .await
-        .unwrap();
-    let info = serde_json::from_str::<kraken::rest_api::response::AssetPairs>(
-        resp.text().await.unwrap().as_str(),
-    )
-    .unwrap();
-    println!("{:#?}", info);
-
-    return;
-
-    let (mut stream, _) = tokio_tungstenite::connect_async("wss://ws.kraken.com")
-        .await
-        .unwrap();
-
-    let pair = "ETH/USD".parse::<CurrencyPair>().unwrap();
+    let pair = "ETH/USD".parse::<CurrencyPair>()?;
     let subscribe = subscribe::Subscribe::builder()
         .pair(vec![pair])
-        .subscription(subscribe::Subscription::builder().name("trade").build())
+        .subscription(
+            subscribe::Subscription::builder()
+                .name(ChannelName::Trade)
+                .build(),
+        )
         .build();
 
-    stream
-        .send(Message::text(serde_json::to_string(&subscribe).unwrap()))
-        .await
-        .unwrap();
+    match client.subscribe(subscribe).await {
+        Ok(status) => println!("{:#?}", status),
+        Err(e) => eprintln!("failed to subscribe: {}", e),
+    }
 
-    while let Some(msg) = stream.next().await {
-        match msg {
-            Ok(Message::Close(_)) => {
-                println!("Closing...");
-                break;
-            }
-            Ok(Message::Text(msg)) => match serde_json::from_str::<Trade>(&msg) {
-                Ok(trade) => println!("{:#?}", trade),
-                Err(_) => {
-                    println!("other message: {}", msg);
-                }
-            },
-            Err(e) => {
-                eprintln!("{:#?}", e);
-                panic!(e);
-            }
-            Ok(other) => {
-                println!("unknown...");
-                println!("{:?}", other);
-            }
+    while let Some(event) = client.next().await {
+        match event {
+            Event::Connected => println!("Connected..."),
+            Event::Disconnected => println!("Disconnected, reconnecting..."),
+            Event::Message(msg) => println!("{:#?}", msg),
+            Event::Invalid { frame, error } => eprintln!("{}: {}", error, frame),
+            Event::BookResync(mismatch) => eprintln!("{}, resubscribing...", mismatch),
+            Event::Latency(latency) => println!("Latency: {:?}", latency),
+            Event::Error(error) => eprintln!("{}", error),
         }
     }
+    Ok(())
 }
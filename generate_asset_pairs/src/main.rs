@@ -1,8 +1,27 @@
-use kraken::rest_api::response::AssetPairs;
+use kraken::{
+    asset_registry::AssetRegistry, message::ParseError, rest_api::response::Response, Error,
+};
 
-fn main() -> failure::Fallible<()> {
-    let req = reqwest::blocking::get("https://api.kraken.com/0/public/AssetPairs")?.text()?;
-    let asset_pairs = serde_json::from_str::<AssetPairs>(req.as_str())?;
+// Caches the `AssetPairs` result for `AssetRegistry::load`.
+// usage: generate_asset_pairs [path (default: asset_pairs.json)]
+fn main() -> Result<(), Error> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "asset_pairs.json".to_string());
 
+    let body = reqwest::blocking::get("https://api.kraken.com/0/public/AssetPairs")?.text()?;
+    let result = serde_json::from_str::<Response<serde_json::Value>>(&body)
+        .map_err(|e| Error::Protocol {
+            input: body.clone(),
+            source: ParseError::Malformed(e),
+        })?
+        .into_result()?;
+    let result = serde_json::to_string_pretty(&result).expect("json values are serializable");
+
+    // make sure the cache can be loaded back before writing it
+    let registry = AssetRegistry::from_json(&result)?;
+    std::fs::write(&path, result)?;
+
+    println!("cached {} asset pairs to {}", registry.len(), path);
     Ok(())
 }
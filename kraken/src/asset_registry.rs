@@ -0,0 +1,230 @@
+use crate::core::CurrencyPair;
+use crate::message::ParseError;
+use crate::rest_api::{self, response::asset_pairs};
+use crate::Error;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    info: asset_pairs::AssetPairInfo,
+}
+
+// Every pair tradable on Kraken, built from the `AssetPairs` endpoint.
+// A pair can be looked up by its raw key (`XXBTZUSD`), its `altname` (`XBTUSD`) or its `wsname`
+// (`XBT/USD`), all of which resolve to the same `CurrencyPair`.
+// Pairs without a `wsname` can't be traded over the websocket API and are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    pairs: HashMap<CurrencyPair, Entry>,
+    names: HashMap<String, CurrencyPair>,
+    skipped: HashMap<String, String>,
+}
+
+impl AssetRegistry {
+    pub fn from_asset_pairs(asset_pairs: asset_pairs::Result) -> Self {
+        let mut registry = Self {
+            skipped: asset_pairs.skipped,
+            ..Self::default()
+        };
+        for (key, info) in asset_pairs.data {
+            let pair = match info
+                .wsname
+                .as_deref()
+                .and_then(|wsname| wsname.parse::<CurrencyPair>().ok())
+            {
+                Some(pair) => pair,
+                None => continue,
+            };
+
+            for name in [key.clone(), info.altname.clone(), pair.to_string()] {
+                registry.names.insert(name, pair.clone());
+            }
+            registry.pairs.insert(pair, Entry { key, info });
+        }
+        registry
+    }
+
+    // `json` is the `result` object of an `AssetPairs` response, as cached by
+    // `generate_asset_pairs`.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str::<asset_pairs::Result>(json)
+            .map(Self::from_asset_pairs)
+            .map_err(|e| Error::protocol(json, ParseError::Malformed(e)))
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub async fn fetch(client: &rest_api::Client) -> Result<Self, Error> {
+        client.asset_pairs().await.map(Self::from_asset_pairs)
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<&CurrencyPair> {
+        self.names.get(name)
+    }
+
+    pub fn info(&self, pair: &CurrencyPair) -> Option<&asset_pairs::AssetPairInfo> {
+        self.pairs.get(pair).map(|entry| &entry.info)
+    }
+
+    // The raw key the REST API reports results under, e.g. `XXBTZUSD`.
+    pub fn key(&self, pair: &CurrencyPair) -> Option<&str> {
+        self.pairs.get(pair).map(|entry| entry.key.as_str())
+    }
+
+    // The raw keys of the pairs whose info couldn't be parsed, with the reason.
+    pub fn skipped(&self) -> &HashMap<String, String> {
+        &self.skipped
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = &CurrencyPair> {
+        self.pairs.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::Asset;
+    use crate::cryptocurrency::Cryptocurrency;
+
+    const ASSET_PAIRS: &str = r#"{
+        "XETHXXBT": {
+            "altname": "ETHXBT",
+            "wsname": "ETH/XBT",
+            "aclass_base": "currency",
+            "base": "XETH",
+            "aclass_quote": "currency",
+            "quote": "XXBT",
+            "lot": "unit",
+            "pair_decimals": 5,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [2, 3, 4, 5],
+            "leverage_sell": [2, 3, 4, 5],
+            "fees": [[0, 0.26], [50000, 0.24]],
+            "fees_maker": [[0, 0.16], [50000, 0.14]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40,
+            "ordermin": "0.02"
+        },
+        "XETHXXBT.d": {
+            "altname": "ETHXBT.d",
+            "aclass_base": "currency",
+            "base": "XETH",
+            "aclass_quote": "currency",
+            "quote": "XXBT",
+            "lot": "unit",
+            "pair_decimals": 5,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [],
+            "leverage_sell": [],
+            "fees": [[0, 0.26]],
+            "fees_maker": [[0, 0.16]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40,
+            "ordermin": "0.02"
+        },
+        "XXBTZUSD": {
+            "altname": "XBTUSD",
+            "wsname": "XBT/USD",
+            "aclass_base": "currency",
+            "base": "XXBT",
+            "aclass_quote": "currency",
+            "quote": "ZUSD",
+            "lot": "unit",
+            "pair_decimals": 1,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [2, 3, 4, 5],
+            "leverage_sell": [2, 3, 4, 5],
+            "fees": [[0, 0.26], [50000, 0.24]],
+            "fees_maker": [[0, 0.16], [50000, 0.14]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40,
+            "ordermin": "0.0001"
+        },
+        "NEWUSD": {
+            "altname": "NEWUSD",
+            "wsname": "NEW/USD",
+            "aclass_base": "currency",
+            "base": "NEW",
+            "aclass_quote": "currency",
+            "quote": "ZUSD",
+            "lot": "unit",
+            "pair_decimals": 4,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [],
+            "leverage_sell": [],
+            "fees": [[0, 0.26]],
+            "fees_maker": [[0, 0.16]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40,
+            "ordermin": "1"
+        },
+        "BADUSD": {
+            "altname": "BADUSD",
+            "wsname": "BAD/USD",
+            "aclass_base": "currency",
+            "base": "BAD",
+            "aclass_quote": "currency",
+            "quote": "ZUSD",
+            "lot": "unit",
+            "pair_decimals": 4,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [],
+            "leverage_sell": [],
+            "fees": [[0, 0.26]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40
+        }
+    }"#;
+
+    #[test]
+    fn test_asset_registry() {
+        let registry = AssetRegistry::from_json(ASSET_PAIRS).unwrap();
+        assert_eq!(registry.len(), 3);
+
+        let pair = registry.resolve("XXBTZUSD").unwrap().clone();
+        assert_eq!(registry.resolve("XBTUSD"), Some(&pair));
+        assert_eq!(registry.resolve("XBT/USD"), Some(&pair));
+        assert_eq!(registry.key(&pair), Some("XXBTZUSD"));
+        assert_eq!(registry.info(&pair).unwrap().altname, "XBTUSD");
+
+        // crypto quoted
+        let pair = registry.resolve("ETHXBT").unwrap();
+        assert_eq!(pair.quote, Asset::Cryptocurrency(Cryptocurrency::XBT));
+        assert_eq!(registry.resolve("ETH/XBT"), Some(pair));
+
+        // not in the static tables
+        let pair = registry.resolve("NEW/USD").unwrap();
+        assert_eq!(pair.base, Asset::Other("NEW".to_string()));
+
+        // dark pool pairs are skipped
+        assert!(registry.resolve("ETHXBT.d").is_none());
+        assert!(registry.resolve("ADA/USD").is_none());
+
+        // as are pairs that don't parse, but not silently
+        assert!(registry.resolve("BAD/USD").is_none());
+        assert_eq!(registry.skipped().len(), 1);
+        assert!(registry.skipped()["BADUSD"].contains("ordermin"));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::core::KrakenFloat;
+use crate::message::publication::{ohlc, trade};
+use crate::rest_api::response::Timestamp;
+use crate::Error;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::collections::BTreeMap;
+
+const MICROS: i64 = 1_000_000;
+
+// The time of a trade, which is a `KrakenFloat` over the websocket API and a `Timestamp` over REST.
+pub trait TradeTime: std::fmt::Debug {
+    // Microseconds since the unix epoch.
+    fn micros(&self) -> Option<i64>;
+}
+
+impl TradeTime for KrakenFloat {
+    fn micros(&self) -> Option<i64> {
+        (Decimal::from(*self) * Decimal::from(MICROS))
+            .trunc()
+            .to_i64()
+    }
+}
+
+impl TradeTime for Timestamp {
+    fn micros(&self) -> Option<i64> {
+        Some(self.0.timestamp_micros())
+    }
+}
+
+fn seconds(micros: i64) -> KrakenFloat {
+    KrakenFloat::new(micros, 6)
+}
+
+#[derive(Debug, Clone)]
+struct Candle {
+    start: i64,
+    // the times of the trades `open` and `close` came from
+    first: i64,
+    last: i64,
+    open: Decimal,
+    high: Decimal,
+    low: Decimal,
+    close: Decimal,
+    volume: Decimal,
+    notional: Decimal,
+    count: i64,
+}
+
+impl Candle {
+    fn new(start: i64, time: i64, price: Decimal, volume: Decimal) -> Self {
+        Self {
+            start,
+            first: time,
+            last: time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume,
+            notional: price * volume,
+            count: 1,
+        }
+    }
+
+    // Trades can arrive out of order, `open` and `close` follow the trade times rather than the
+    // order they were added in.
+    fn add(&mut self, time: i64, price: Decimal, volume: Decimal) {
+        if time < self.first {
+            self.first = time;
+            self.open = price;
+        }
+        if time >= self.last {
+            self.last = time;
+            self.close = price;
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.volume += volume;
+        self.notional += price * volume;
+        self.count += 1;
+    }
+
+    fn data(&self, interval: i64) -> ohlc::Data {
+        let vwap = if self.volume.is_zero() {
+            self.close
+        } else {
+            self.notional / self.volume
+        };
+        ohlc::Data {
+            time: seconds(self.start),
+            etime: seconds(self.start + interval),
+            open: self.open.into(),
+            high: self.high.into(),
+            low: self.low.into(),
+            close: self.close.into(),
+            vwap: vwap.into(),
+            volume: self.volume.into(),
+            count: self.count,
+        }
+    }
+}
+
+// Builds OHLC candles of any interval out of trades, laid out like Kraken's own `ohlc` data with
+// `time`/`etime` being the start/end of the interval.
+// Intervals are aligned to the unix epoch and run from `time` inclusive to `etime` exclusive.
+// Intervals without any trades produce no candle.
+//
+// A candle is emitted once a trade at least `lateness` past its end arrives, until then trades
+// arriving out of order are still added to it. Trades for a candle that was already emitted are
+// rejected.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    interval: i64,
+    lateness: i64,
+    candles: BTreeMap<i64, Candle>,
+    // the end of the last emitted interval, anything before is late
+    emitted: Option<i64>,
+}
+
+impl CandleAggregator {
+    pub fn new(interval: chrono::Duration) -> Result<Self, Error> {
+        match interval.num_microseconds() {
+            Some(interval) if interval > 0 => Ok(Self {
+                interval,
+                lateness: 0,
+                candles: BTreeMap::new(),
+                emitted: None,
+            }),
+            _ => Err(Error::parse(
+                interval.to_string(),
+                "candle interval must be positive",
+            )),
+        }
+    }
+
+    // How long after its end a candle still accepts trades.
+    pub fn with_lateness(mut self, lateness: chrono::Duration) -> Self {
+        self.lateness = lateness.num_microseconds().unwrap_or(i64::MAX).max(0);
+        self
+    }
+
+    // Adds a trade, returning the candles it closed in chronological order.
+    pub fn push<T: TradeTime>(&mut self, trade: &trade::Data<T>) -> Result<Vec<ohlc::Data>, Error> {
+        let invalid = |reason| Error::parse(format!("{:?}", trade.time), reason);
+        let time = trade
+            .time
+            .micros()
+            .ok_or_else(|| invalid("invalid trade time"))?;
+        if self.emitted.is_some_and(|emitted| time < emitted) {
+            return Err(invalid(
+                "trade belongs to a candle that was already emitted",
+            ));
+        }
+
+        let (price, volume) = (Decimal::from(trade.price), Decimal::from(trade.volume));
+        let start = time.div_euclid(self.interval) * self.interval;
+        self.candles
+            .entry(start)
+            .and_modify(|candle| candle.add(time, price, volume))
+            .or_insert_with(|| Candle::new(start, time, price, volume));
+
+        Ok(self.close_until(time.saturating_sub(self.lateness)))
+    }
+
+    // Emits the candles that ended at least `lateness` before `now`, as if a trade had arrived at
+    // `now`. Useful to close candles on a timer when trades are sparse.
+    pub fn close(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<ohlc::Data> {
+        self.close_until(now.timestamp_micros().saturating_sub(self.lateness))
+    }
+
+    // Emits every candle ending at or before `cutoff`.
+    fn close_until(&mut self, cutoff: i64) -> Vec<ohlc::Data> {
+        let boundary = cutoff.div_euclid(self.interval) * self.interval;
+        if self.emitted.is_some_and(|emitted| boundary <= emitted) {
+            return Vec::new();
+        }
+        self.emitted = Some(boundary);
+
+        let open = self.candles.split_off(&boundary);
+        std::mem::replace(&mut self.candles, open)
+            .values()
+            .map(|candle| candle.data(self.interval))
+            .collect()
+    }
+
+    // Emits every candle, including the ones still open, e.g. at the end of a trade capture.
+    pub fn flush(&mut self) -> Vec<ohlc::Data> {
+        match self.candles.keys().next_back() {
+            Some(&start) => self.close_until(start + self.interval),
+            None => Vec::new(),
+        }
+    }
+
+    // The candles not emitted yet, which may still change.
+    pub fn pending(&self) -> impl Iterator<Item = ohlc::Data> + '_ {
+        self.candles
+            .values()
+            .map(move |candle| candle.data(self.interval))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trade(time: &str, price: &str, volume: &str) -> trade::Data<KrakenFloat> {
+        serde_json::from_str(&format!(
+            r#"["{}","{}","{}","b","l",""]"#,
+            price, volume, time
+        ))
+        .unwrap()
+    }
+
+    fn strings(data: &ohlc::Data) -> Vec<String> {
+        vec![
+            data.time,
+            data.etime,
+            data.open,
+            data.high,
+            data.low,
+            data.close,
+            data.vwap,
+            data.volume,
+        ]
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_candles() {
+        let mut candles = CandleAggregator::new(chrono::Duration::seconds(90)).unwrap();
+        assert!(candles
+            .push(&trade("90.5", "10.0", "1.0"))
+            .unwrap()
+            .is_empty());
+        assert!(candles
+            .push(&trade("100.0", "12.0", "1.0"))
+            .unwrap()
+            .is_empty());
+        assert!(candles
+            .push(&trade("110.0", "9.0", "2.0"))
+            .unwrap()
+            .is_empty());
+
+        // a trade on the boundary opens the next candle and closes the previous one
+        let closed = candles.push(&trade("180.0", "11.0", "1.0")).unwrap();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(
+            strings(&closed[0]),
+            vec![
+                "90.000000",
+                "180.000000",
+                "10.0",
+                "12.0",
+                "9.0",
+                "9.0",
+                "10.0",
+                "4.0"
+            ]
+        );
+        assert_eq!(closed[0].count, 3);
+
+        // empty intervals are skipped
+        assert!(candles.push(&trade("400.0", "13.0", "1.0")).unwrap().len() == 1);
+        let pending = candles.pending().collect::<Vec<_>>();
+        assert_eq!(pending[0].time.to_string(), "360.000000");
+
+        assert!(candles.push(&trade("100.0", "1.0", "1.0")).is_err());
+        assert_eq!(candles.flush().len(), 1);
+        assert!(candles.flush().is_empty());
+    }
+
+    #[test]
+    fn test_late_trades() {
+        let mut candles = CandleAggregator::new(chrono::Duration::minutes(1))
+            .unwrap()
+            .with_lateness(chrono::Duration::seconds(10));
+        candles.push(&trade("5.0", "2.0", "1.0")).unwrap();
+        candles.push(&trade("30.0", "3.0", "1.0")).unwrap();
+
+        // the next interval starts, but the first one still takes late trades
+        assert!(candles
+            .push(&trade("65.0", "5.0", "1.0"))
+            .unwrap()
+            .is_empty());
+        assert!(candles
+            .push(&trade("1.0", "1.0", "1.0"))
+            .unwrap()
+            .is_empty());
+        assert!(candles
+            .push(&trade("59.999999", "4.0", "1.0"))
+            .unwrap()
+            .is_empty());
+
+        let closed = candles.push(&trade("70.0", "6.0", "1.0")).unwrap();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open.to_string(), "1.0");
+        assert_eq!(closed[0].close.to_string(), "4.0");
+        assert_eq!(closed[0].count, 4);
+
+        assert!(candles.push(&trade("59.0", "1.0", "1.0")).is_err());
+
+        // without newer trades the open candle is closed by time
+        let now = |secs| chrono::DateTime::from_timestamp(secs, 0).unwrap();
+        assert!(candles.close(now(129)).is_empty());
+        assert_eq!(candles.close(now(130)).len(), 1);
+        assert!(candles.pending().next().is_none());
+    }
+
+    #[test]
+    fn test_rest_trades() {
+        use crate::rest_api::response::trade_history::TradeData;
+
+        let trades: Vec<TradeData> = serde_json::from_str(
+            r#"[["8000.0","0.5",1534614057.321597,"s","m",""],["8010.0","0.5",1534614061.0,"b","l",""]]"#,
+        )
+        .unwrap();
+        let mut candles = CandleAggregator::new(chrono::Duration::seconds(5)).unwrap();
+        let mut closed = Vec::new();
+        for trade in trades.iter() {
+            closed.extend(candles.push(trade).unwrap());
+        }
+        closed.extend(candles.flush());
+        assert_eq!(closed.len(), 2);
+        assert_eq!(closed[0].time.to_string(), "1534614055.000000");
+        assert_eq!(closed[1].close.to_string(), "8010.0");
+    }
+}
@@ -1,46 +1,136 @@
 use crate::cryptocurrency::Cryptocurrency;
 use crate::currency::Currency;
+use crate::Error;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 
-// THIS IS SO ANNOYING!!! :(
-#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, PartialOrd)]
+// Prices, volumes and timestamps are sent as decimal strings, e.g. "8552.90000".
+// They are kept as exact decimals with the scale they were sent with, so they serialize back
+// exactly as received and can be summed without accumulating rounding errors.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(try_from = "&str", into = "String")]
-pub struct KrakenFloat(f64);
+pub struct KrakenFloat(Decimal);
 
-impl Into<String> for KrakenFloat {
-    fn into(self) -> String {
-        format!("{}", self.0)
+impl KrakenFloat {
+    // `num * 10^-scale`, e.g. `KrakenFloat::new(85529, 1)` is 8552.9
+    pub fn new(num: i64, scale: u32) -> Self {
+        Self(Decimal::new(num, scale))
+    }
+
+    // The number of decimal places, e.g. 5 for "8552.90000".
+    pub fn scale(&self) -> u32 {
+        self.0.scale()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        self.0.checked_mul(other.0).map(Self)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        self.0.checked_div(other.0).map(Self)
+    }
+
+    // Rounds half away from zero to at most `decimals` decimal places.
+    pub fn round_dp(self, decimals: u32) -> Self {
+        Self(
+            self.0
+                .round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero),
+        )
+    }
+
+    // Truncates to at most `decimals` decimal places.
+    pub fn trunc_dp(self, decimals: u32) -> Self {
+        Self(
+            self.0
+                .round_dp_with_strategy(decimals, RoundingStrategy::ToZero),
+        )
+    }
+
+    // Lossy, only meant for display or statistics.
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl From<KrakenFloat> for String {
+    fn from(kf: KrakenFloat) -> Self {
+        kf.0.to_string()
     }
 }
 
 impl std::convert::TryFrom<&str> for KrakenFloat {
-    type Error = std::num::ParseFloatError;
+    type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(value.parse::<KrakenFloat>()?)
+        value.parse::<KrakenFloat>()
     }
 }
 
 impl std::str::FromStr for KrakenFloat {
-    type Err = std::num::ParseFloatError;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let float = s.parse::<f64>()?;
-        Ok(Self(float))
+        s.parse::<Decimal>()
+            .map(Self)
+            .map_err(|_| Error::parse(s, "invalid decimal"))
     }
 }
 
-impl std::ops::Deref for KrakenFloat {
-    type Target = f64;
+impl From<Decimal> for KrakenFloat {
+    fn from(decimal: Decimal) -> Self {
+        Self(decimal)
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl From<KrakenFloat> for Decimal {
+    fn from(kf: KrakenFloat) -> Self {
+        kf.0
     }
 }
 
-impl From<f64> for KrakenFloat {
-    fn from(float: f64) -> Self {
-        Self(float)
+macro_rules! kraken_float_from_int {
+    ($($int:ty),*) => {
+        $(
+            impl From<$int> for KrakenFloat {
+                fn from(int: $int) -> Self {
+                    Self(Decimal::from(int))
+                }
+            }
+        )*
+    };
+}
+
+kraken_float_from_int!(i32, i64, u32, u64);
+
+// Goes through the shortest representation of the float, so `0.1` becomes exactly "0.1".
+impl std::convert::TryFrom<f64> for KrakenFloat {
+    type Error = Error;
+
+    fn try_from(float: f64) -> Result<Self, Self::Error> {
+        if !float.is_finite() {
+            return Err(Error::parse(
+                float.to_string(),
+                "KrakenFloat must be finite",
+            ));
+        }
+        float
+            .to_string()
+            .parse::<Decimal>()
+            .map(Self)
+            .map_err(|_| Error::parse(float.to_string(), "float out of range for KrakenFloat"))
     }
 }
 
@@ -52,7 +142,7 @@ impl std::fmt::Debug for KrakenFloat {
 
 impl std::fmt::Display for KrakenFloat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}kf", self.0)
+        write!(f, "{}", self.0)
     }
 }
 
@@ -62,38 +152,144 @@ impl Unpin for KrakenFloat {}
 impl std::panic::UnwindSafe for KrakenFloat {}
 impl std::panic::RefUnwindSafe for KrakenFloat {}
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+// Any asset traded on Kraken.
+// Assets missing from the static `Cryptocurrency`/`Currency` tables, e.g. newly listed coins,
+// are kept by name.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+#[serde(into = "String", try_from = "&str")]
+pub enum Asset {
+    Cryptocurrency(Cryptocurrency),
+    Currency(Currency),
+    Other(String),
+}
+
+impl Asset {
+    pub fn name(&self) -> &str {
+        match self {
+            Asset::Cryptocurrency(cryptocurrency) => cryptocurrency.abrv(),
+            Asset::Currency(currency) => currency.alpha3(),
+            Asset::Other(name) => name,
+        }
+    }
+
+    // Parses the codes the REST API reports balances and ledgers in, where older assets carry an
+    // `X` (cryptocurrencies) or `Z` (fiat) prefix, e.g. "XXBT", "ZUSD" or "DOT".
+    pub fn from_code(code: &str) -> Result<Self, Error> {
+        if code == "XDG" || code == "XXDG" {
+            return Ok(Asset::Cryptocurrency(Cryptocurrency::DOGE));
+        }
+        if code.len() == 4 {
+            if let Some(cryptocurrency) = code
+                .strip_prefix('X')
+                .and_then(|abrv| abrv.parse::<Cryptocurrency>().ok())
+            {
+                return Ok(Asset::Cryptocurrency(cryptocurrency));
+            }
+            if let Some(currency) = code
+                .strip_prefix('Z')
+                .and_then(|alpha3| alpha3.parse::<Currency>().ok())
+            {
+                return Ok(Asset::Currency(currency));
+            }
+        }
+        code.parse()
+    }
+}
+
+impl std::convert::TryFrom<&str> for Asset {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Ok(cryptocurrency) = value.parse::<Cryptocurrency>() {
+            Ok(Asset::Cryptocurrency(cryptocurrency))
+        } else if let Ok(currency) = value.parse::<Currency>() {
+            Ok(Asset::Currency(currency))
+        } else if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '.')
+        {
+            Ok(Asset::Other(value.to_string()))
+        } else {
+            Err(Error::parse(value, "invalid asset"))
+        }
+    }
+}
+
+impl std::str::FromStr for Asset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        std::convert::TryFrom::try_from(s)
+    }
+}
+
+impl From<Cryptocurrency> for Asset {
+    fn from(cryptocurrency: Cryptocurrency) -> Self {
+        Asset::Cryptocurrency(cryptocurrency)
+    }
+}
+
+impl From<Currency> for Asset {
+    fn from(currency: Currency) -> Self {
+        Asset::Currency(currency)
+    }
+}
+
+impl From<Asset> for String {
+    fn from(asset: Asset) -> Self {
+        asset.name().to_string()
+    }
+}
+
+impl std::fmt::Display for Asset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// A pair in Kraken's websocket notation, e.g. "XBT/USD" or "ETH/XBT".
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 #[serde(into = "String", try_from = "&str")]
 pub struct CurrencyPair {
-    pub cryptocurrency: Cryptocurrency,
-    pub currency: Currency,
+    pub base: Asset,
+    pub quote: Asset,
+}
+
+impl CurrencyPair {
+    pub fn new<B: Into<Asset>, Q: Into<Asset>>(base: B, quote: Q) -> Self {
+        Self {
+            base: base.into(),
+            quote: quote.into(),
+        }
+    }
 }
 
 impl std::convert::TryFrom<&str> for CurrencyPair {
-    type Error = &'static str;
+    type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut split = value.split("/");
+        let mut split = value.split('/');
 
-        let cryptocurrency = match split.next() {
-            Some(s) => s.parse::<Cryptocurrency>()?,
-            None => return Err("missing cryptocurrency"),
-        };
+        let base = split
+            .next()
+            .and_then(|s| s.parse::<Asset>().ok())
+            .ok_or_else(|| Error::parse(value, "invalid base asset"))?;
 
-        let currency = match split.next() {
-            Some(s) => s.parse::<Currency>()?,
-            None => return Err("missing currency"),
+        let quote = match split.next() {
+            Some(s) => s
+                .parse::<Asset>()
+                .map_err(|_| Error::parse(value, "invalid quote asset"))?,
+            None => return Err(Error::parse(value, "missing quote asset")),
         };
 
-        Ok(Self {
-            cryptocurrency,
-            currency,
-        })
+        if split.next().is_some() {
+            return Err(Error::parse(value, "too many assets in currency pair"));
+        }
+
+        Ok(Self { base, quote })
     }
 }
 
 impl std::str::FromStr for CurrencyPair {
-    type Err = &'static str;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         std::convert::TryFrom::try_from(s)
@@ -102,18 +298,13 @@ impl std::str::FromStr for CurrencyPair {
 
 impl From<CurrencyPair> for String {
     fn from(cp: CurrencyPair) -> Self {
-        format!("{}/{}", cp.cryptocurrency.abrv(), cp.currency.alpha3())
+        cp.to_string()
     }
 }
 
 impl std::fmt::Display for CurrencyPair {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}/{}",
-            self.cryptocurrency.abrv(),
-            self.currency.alpha3()
-        )
+        write!(f, "{}/{}", self.base, self.quote)
     }
 }
 
@@ -127,6 +318,44 @@ impl std::panic::RefUnwindSafe for CurrencyPair {}
 mod test {
     use super::*;
 
+    #[test]
+    fn test_kraken_float() {
+        use std::convert::TryFrom;
+
+        let price = "8552.90000".parse::<KrakenFloat>().unwrap();
+        assert_eq!(price.scale(), 5);
+        assert_eq!(price.to_string(), "8552.90000");
+        assert_eq!(String::from(price), "8552.90000");
+        assert_eq!(price, KrakenFloat::new(85529, 1));
+        assert!("invalid".parse::<KrakenFloat>().is_err());
+
+        // no rounding error accumulates
+        let tenth = KrakenFloat::try_from(0.1).unwrap();
+        let sum = (0..10).fold(KrakenFloat::default(), |sum, _| {
+            sum.checked_add(tenth).unwrap()
+        });
+        assert_eq!(sum, KrakenFloat::from(1));
+
+        let a = KrakenFloat::new(15, 1);
+        let b = KrakenFloat::from(2);
+        assert_eq!(a.checked_sub(b), Some(KrakenFloat::new(-5, 1)));
+        assert_eq!(a.checked_mul(b), Some(KrakenFloat::from(3)));
+        assert_eq!(a.checked_div(b), Some(KrakenFloat::new(75, 2)));
+        assert_eq!(a.checked_div(KrakenFloat::default()), None);
+        assert!(a < b);
+
+        assert!(KrakenFloat::try_from(f64::NAN).is_err());
+
+        let value = KrakenFloat::new(123455, 4);
+        assert_eq!(value.round_dp(3), KrakenFloat::new(12346, 3));
+        assert_eq!(value.trunc_dp(3), KrakenFloat::new(12345, 3));
+        assert_eq!(value.round_dp(6).to_string(), "12.3455");
+
+        let json = serde_json::to_string(&price).unwrap();
+        assert_eq!(json, r#""8552.90000""#);
+        assert_eq!(serde_json::from_str::<KrakenFloat>(&json).unwrap(), price);
+    }
+
     #[test]
     fn test_currency_pair() {
         let invalid = "INVALID";
@@ -136,7 +365,60 @@ mod test {
         assert!(cp.is_err());
 
         let cp = valid.parse::<CurrencyPair>().unwrap();
-        assert_eq!(cp.cryptocurrency.abrv(), "ETH");
-        assert_eq!(cp.currency.alpha3(), "USD");
+        assert_eq!(cp.base, Asset::Cryptocurrency(Cryptocurrency::ETH));
+        assert_eq!(cp.quote.name(), "USD");
+        assert_eq!(cp.to_string(), valid);
+
+        // crypto quoted
+        let cp = "ETH/XBT".parse::<CurrencyPair>().unwrap();
+        assert_eq!(cp.quote, Asset::Cryptocurrency(Cryptocurrency::XBT));
+
+        // not in the static tables
+        let cp = "NEWCOIN/EUR".parse::<CurrencyPair>().unwrap();
+        assert_eq!(cp.base, Asset::Other("NEWCOIN".to_string()));
+        assert_eq!(String::from(cp), "NEWCOIN/EUR");
+
+        assert!("ETH/".parse::<CurrencyPair>().is_err());
+        assert!("ETH/USD/EUR".parse::<CurrencyPair>().is_err());
+    }
+
+    #[test]
+    fn test_asset_code() {
+        assert_eq!(
+            Asset::from_code("XXBT").unwrap(),
+            Asset::Cryptocurrency(Cryptocurrency::XBT)
+        );
+        assert_eq!(
+            Asset::from_code("XETH").unwrap(),
+            Asset::Cryptocurrency(Cryptocurrency::ETH)
+        );
+        assert_eq!(
+            Asset::from_code("XXDG").unwrap(),
+            Asset::Cryptocurrency(Cryptocurrency::DOGE)
+        );
+        assert_eq!(Asset::from_code("ZUSD").unwrap().name(), "USD");
+        assert!(matches!(Asset::from_code("EUR"), Ok(Asset::Currency(_))));
+
+        // newer assets have no prefix
+        assert_eq!(
+            Asset::from_code("DOT").unwrap(),
+            Asset::Cryptocurrency(Cryptocurrency::DOT)
+        );
+        assert_eq!(
+            Asset::from_code("USDT").unwrap(),
+            Asset::Cryptocurrency(Cryptocurrency::USDT)
+        );
+        assert_eq!(Asset::from_code("XTZ").unwrap().name(), "XTZ");
+
+        // staked and unknown assets are kept by name
+        assert_eq!(
+            Asset::from_code("ETH2.S").unwrap(),
+            Asset::Other("ETH2.S".to_string())
+        );
+        assert_eq!(
+            Asset::from_code("KFEE").unwrap(),
+            Asset::Other("KFEE".to_string())
+        );
+        assert!(Asset::from_code("").is_err());
     }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
 #[serde(try_from = "&str", into = "&str")]
 pub enum Cryptocurrency {
     ZRX = 0,
@@ -70,6 +70,8 @@ impl Cryptocurrency {
         CRYPTOCURRENCY_DATA[*self as usize].name
     }
 
+    /// # Safety
+    /// `s` must be a known cryptocurrency abbreviation, otherwise this panics.
     pub unsafe fn from_str_unchecked<S: AsRef<str>>(s: S) -> Self {
         CRYPTOCURRENCY_DATA
             .iter()
@@ -80,7 +82,7 @@ impl Cryptocurrency {
 }
 
 impl std::str::FromStr for Cryptocurrency {
-    type Err = &'static str;
+    type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         std::convert::TryFrom::try_from(s)
@@ -88,13 +90,13 @@ impl std::str::FromStr for Cryptocurrency {
 }
 
 impl std::convert::TryFrom<&str> for Cryptocurrency {
-    type Error = &'static str;
+    type Error = crate::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         if let Some(data) = CRYPTOCURRENCY_DATA.iter().find(|data| data.abrv == value) {
             Ok(data.val)
         } else {
-            Err("Invalid cryptocurrency")
+            Err(crate::Error::parse(value, "unknown cryptocurrency"))
         }
     }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, PartialOrd, Serialize, Deserialize, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize, Copy, Clone)]
 #[serde(try_from = "&str", into = "&str")]
 pub struct Currency(usize);
 
@@ -25,6 +25,8 @@ impl Currency {
         iso4217::all()[self.0].num
     }
 
+    /// # Safety
+    /// `s` must be a valid ISO-4217 alpha3 code, otherwise this panics.
     pub unsafe fn from_str_unchecked<S: AsRef<str>>(s: S) -> Self {
         Self(
             iso4217::all()
@@ -44,7 +46,7 @@ impl std::fmt::Debug for Currency {
 }
 
 impl std::convert::TryFrom<&str> for Currency {
-    type Error = &'static str;
+    type Error = crate::Error;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         iso4217::all()
@@ -52,12 +54,12 @@ impl std::convert::TryFrom<&str> for Currency {
             .enumerate()
             .find(|(_, curr)| curr.alpha3 == s)
             .map(|(idx, _)| Currency(idx))
-            .ok_or("Invalid currency")
+            .ok_or_else(|| crate::Error::parse(s, "unknown currency"))
     }
 }
 
 impl std::str::FromStr for Currency {
-    type Err = &'static str;
+    type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         std::convert::TryFrom::try_from(s)
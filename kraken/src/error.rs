@@ -0,0 +1,157 @@
+use crate::message::ParseError;
+use crate::rest_api::response;
+
+// The error of every fallible API of the crate.
+#[derive(Debug)]
+pub enum Error {
+    // `input` isn't a valid value, e.g. an unknown currency pair or a candle interval of zero.
+    Parse { input: String, reason: &'static str },
+    // `input`, a frame or response body sent by Kraken, doesn't follow its API.
+    Protocol { input: String, source: ParseError },
+    // The request never got an answer from Kraken.
+    Transport(Transport),
+    // Kraken answered, but rejected the request.
+    Exchange(response::Error),
+    // No response arrived in time, the request may still have been processed.
+    Timeout,
+    // The request would exceed a `RateLimiter` in `Mode::FailFast`, and may be sent after the wait.
+    RateLimited(std::time::Duration),
+    // A private endpoint was called without credentials.
+    MissingCredentials,
+    // Reading or writing a local file, e.g. a recording or the asset pairs cache.
+    Io(std::io::Error),
+}
+
+#[derive(Debug)]
+pub enum Transport {
+    Http(reqwest::Error),
+    // The `ws::Client` connection task has stopped.
+    Closed,
+    // The websocket connection dropped before the response arrived.
+    Disconnected,
+}
+
+impl Error {
+    pub(crate) fn parse<S: Into<String>>(input: S, reason: &'static str) -> Self {
+        Error::Parse {
+            input: input.into(),
+            reason,
+        }
+    }
+
+    pub(crate) fn protocol<S: Into<String>>(input: S, source: ParseError) -> Self {
+        Error::Protocol {
+            input: input.into(),
+            source,
+        }
+    }
+
+    // Whether the request wasn't processed and may succeed if sent again later, orders included.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Transport(Transport::Http(e)) => e.is_connect(),
+            Error::RateLimited(_) => true,
+            Error::Exchange(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    // Whether the request may or may not have been processed, e.g. it timed out. Idempotent
+    // requests can be sent again, orders should be looked up first.
+    pub fn is_outcome_unknown(&self) -> bool {
+        match self {
+            Error::Transport(Transport::Http(e)) => !e.is_connect(),
+            Error::Transport(Transport::Disconnected) | Error::Timeout => true,
+            Error::Exchange(e) => e.is_outcome_unknown(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse { input, reason } => write!(f, "invalid `{}`: {}", input, reason),
+            Error::Protocol { input, source } => write!(f, "{} in `{}`", source, input),
+            Error::Transport(e) => write!(f, "{}", e),
+            Error::Exchange(e) => write!(f, "kraken error: {}", e),
+            Error::Timeout => write!(f, "timed out waiting for a response"),
+            Error::RateLimited(wait) => write!(f, "rate limited, retry in {:?}", wait),
+            Error::MissingCredentials => write!(f, "private endpoint requires credentials"),
+            Error::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Protocol { source, .. } => Some(source),
+            Error::Transport(Transport::Http(e)) => Some(e),
+            Error::Exchange(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Http(e) => write!(f, "http error: {}", e),
+            Transport::Closed => write!(f, "ws::Client connection task has stopped"),
+            Transport::Disconnected => write!(f, "disconnected before receiving a response"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(Transport::Http(e))
+    }
+}
+
+impl From<response::Error> for Error {
+    fn from(e: response::Error) -> Self {
+        Error::Exchange(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        let error = "XBT/US$".parse::<crate::core::CurrencyPair>().unwrap_err();
+        assert!(matches!(&error, Error::Parse { input, .. } if input == "XBT/US$"));
+        assert_eq!(error.to_string(), "invalid `XBT/US$`: invalid quote asset");
+        assert!(!error.is_retryable());
+
+        let error = "[1,2".parse::<crate::message::Message>().unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::Protocol { input, source: ParseError::Malformed(_) } if input == "[1,2"
+        ));
+        assert!(std::error::Error::source(&error).is_some());
+
+        let error = Error::from(response::Error::Api(
+            vec!["EAPI:Rate limit exceeded".into()],
+        ));
+        assert!(error.is_retryable());
+        assert!(!error.is_outcome_unknown());
+        let error = Error::from(response::Error::Api(vec!["EService:Busy".into()]));
+        assert!(!error.is_retryable());
+        assert!(error.is_outcome_unknown());
+        assert!(Error::Transport(Transport::Disconnected).is_outcome_unknown());
+        assert!(Error::Timeout.is_outcome_unknown());
+        assert!(!Error::Transport(Transport::Closed).is_retryable());
+        assert!(!Error::Transport(Transport::Closed).is_outcome_unknown());
+    }
+}
@@ -1,8 +1,16 @@
 #[macro_use]
 extern crate typed_builder;
 
+pub mod asset_registry;
+pub mod candles;
 pub mod core;
 pub mod cryptocurrency;
 pub mod currency;
+pub mod error;
 pub mod message;
+pub mod order_book;
+pub mod rate_limit;
 pub mod rest_api;
+pub mod ws;
+
+pub use error::Error;
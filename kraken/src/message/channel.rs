@@ -0,0 +1,260 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+// The channels that can be subscribed to, `All` (`*`) covers every channel available.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelName {
+    #[serde(rename = "ticker")]
+    Ticker,
+    #[serde(rename = "ohlc")]
+    Ohlc,
+    #[serde(rename = "trade")]
+    Trade,
+    #[serde(rename = "spread")]
+    Spread,
+    #[serde(rename = "book")]
+    Book,
+    #[serde(rename = "ownTrades")]
+    OwnTrades,
+    #[serde(rename = "openOrders")]
+    OpenOrders,
+    #[serde(rename = "*")]
+    All,
+}
+
+impl ChannelName {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelName::Ticker => "ticker",
+            ChannelName::Ohlc => "ohlc",
+            ChannelName::Trade => "trade",
+            ChannelName::Spread => "spread",
+            ChannelName::Book => "book",
+            ChannelName::OwnTrades => "ownTrades",
+            ChannelName::OpenOrders => "openOrders",
+            ChannelName::All => "*",
+        }
+    }
+
+    // Private channels require a WebSocket token.
+    pub fn is_private(&self) -> bool {
+        matches!(self, ChannelName::OwnTrades | ChannelName::OpenOrders)
+    }
+}
+
+impl std::str::FromStr for ChannelName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ticker" => Ok(ChannelName::Ticker),
+            "ohlc" => Ok(ChannelName::Ohlc),
+            "trade" => Ok(ChannelName::Trade),
+            "spread" => Ok(ChannelName::Spread),
+            "book" => Ok(ChannelName::Book),
+            "ownTrades" => Ok(ChannelName::OwnTrades),
+            "openOrders" => Ok(ChannelName::OpenOrders),
+            "*" => Ok(ChannelName::All),
+            _ => Err(Error::parse(s, "invalid ChannelName")),
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// The `ohlc` candle intervals, sent in minutes.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "i64", into = "i64")]
+pub enum Interval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
+    OneHour,
+    FourHours,
+    OneDay,
+    OneWeek,
+    FifteenDays,
+}
+
+impl Interval {
+    pub fn minutes(&self) -> i64 {
+        match self {
+            Interval::OneMinute => 1,
+            Interval::FiveMinutes => 5,
+            Interval::FifteenMinutes => 15,
+            Interval::ThirtyMinutes => 30,
+            Interval::OneHour => 60,
+            Interval::FourHours => 240,
+            Interval::OneDay => 1440,
+            Interval::OneWeek => 10080,
+            Interval::FifteenDays => 21600,
+        }
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.minutes())
+    }
+}
+
+impl TryFrom<i64> for Interval {
+    type Error = Error;
+
+    fn try_from(minutes: i64) -> Result<Self, Self::Error> {
+        match minutes {
+            1 => Ok(Interval::OneMinute),
+            5 => Ok(Interval::FiveMinutes),
+            15 => Ok(Interval::FifteenMinutes),
+            30 => Ok(Interval::ThirtyMinutes),
+            60 => Ok(Interval::OneHour),
+            240 => Ok(Interval::FourHours),
+            1440 => Ok(Interval::OneDay),
+            10080 => Ok(Interval::OneWeek),
+            21600 => Ok(Interval::FifteenDays),
+            _ => Err(Error::parse(minutes.to_string(), "invalid Interval")),
+        }
+    }
+}
+
+impl From<Interval> for i64 {
+    fn from(interval: Interval) -> Self {
+        interval.minutes()
+    }
+}
+
+// The number of `book` levels per side.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "i64", into = "i64")]
+pub enum Depth {
+    D10,
+    D25,
+    D100,
+    D500,
+    D1000,
+}
+
+impl Depth {
+    pub fn levels(&self) -> usize {
+        match self {
+            Depth::D10 => 10,
+            Depth::D25 => 25,
+            Depth::D100 => 100,
+            Depth::D500 => 500,
+            Depth::D1000 => 1000,
+        }
+    }
+}
+
+impl TryFrom<i64> for Depth {
+    type Error = Error;
+
+    fn try_from(levels: i64) -> Result<Self, Self::Error> {
+        match levels {
+            10 => Ok(Depth::D10),
+            25 => Ok(Depth::D25),
+            100 => Ok(Depth::D100),
+            500 => Ok(Depth::D500),
+            1000 => Ok(Depth::D1000),
+            _ => Err(Error::parse(levels.to_string(), "invalid Depth")),
+        }
+    }
+}
+
+impl TryFrom<usize> for Depth {
+    type Error = Error;
+
+    fn try_from(levels: usize) -> Result<Self, Self::Error> {
+        Depth::try_from(levels as i64)
+    }
+}
+
+impl From<Depth> for i64 {
+    fn from(depth: Depth) -> Self {
+        depth.levels() as i64
+    }
+}
+
+// A value echoed back by a `SubscriptionStatus`, which is `Invalid` if the `Subscribe` asked
+// for something Kraken doesn't support and the status is an error, e.g. a book depth of 42.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum Echoed<T, R> {
+    Valid(T),
+    Invalid(R),
+}
+
+impl<T, R> Echoed<T, R> {
+    pub fn valid(&self) -> Option<&T> {
+        match self {
+            Echoed::Valid(value) => Some(value),
+            Echoed::Invalid(_) => None,
+        }
+    }
+}
+
+impl<T, R> From<T> for Echoed<T, R> {
+    fn from(value: T) -> Self {
+        Echoed::Valid(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_channel_name() {
+        let names = vec![
+            (ChannelName::Ticker, r#""ticker""#),
+            (ChannelName::OwnTrades, r#""ownTrades""#),
+            (ChannelName::All, r#""*""#),
+        ];
+        for (name, json) in names {
+            assert_eq!(serde_json::to_string(&name).unwrap(), json);
+            assert_eq!(serde_json::from_str::<ChannelName>(json).unwrap(), name);
+            assert_eq!(name.as_str().parse::<ChannelName>().unwrap(), name);
+        }
+        assert!(serde_json::from_str::<ChannelName>(r#""trades""#).is_err());
+        assert!(ChannelName::OpenOrders.is_private());
+        assert!(!ChannelName::Book.is_private());
+    }
+
+    #[test]
+    fn test_interval() {
+        assert_eq!(serde_json::to_string(&Interval::OneDay).unwrap(), "1440");
+        assert_eq!(
+            serde_json::from_str::<Interval>("21600").unwrap(),
+            Interval::FifteenDays
+        );
+        assert!(serde_json::from_str::<Interval>("2").is_err());
+        assert_eq!(Interval::FourHours.duration(), chrono::Duration::hours(4));
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(serde_json::to_string(&Depth::D25).unwrap(), "25");
+        assert_eq!(serde_json::from_str::<Depth>("1000").unwrap(), Depth::D1000);
+        assert!(serde_json::from_str::<Depth>("50").is_err());
+        assert_eq!(Depth::try_from(100usize).unwrap(), Depth::D100);
+    }
+
+    #[test]
+    fn test_echoed() {
+        let depth: Echoed<Depth, i64> = serde_json::from_str("25").unwrap();
+        assert_eq!(depth, Echoed::Valid(Depth::D25));
+        assert_eq!(depth.valid(), Some(&Depth::D25));
+
+        let depth: Echoed<Depth, i64> = serde_json::from_str("42").unwrap();
+        assert_eq!(depth, Echoed::Invalid(42));
+        assert_eq!(depth.valid(), None);
+        assert_eq!(serde_json::to_string(&depth).unwrap(), "42");
+
+        let name: Echoed<ChannelName, String> = serde_json::from_str(r#""trades""#).unwrap();
+        assert_eq!(name, Echoed::Invalid("trades".to_string()));
+    }
+}
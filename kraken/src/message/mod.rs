@@ -1,3 +1,233 @@
+pub mod channel;
 pub mod publication;
 pub mod request;
 pub mod response;
+
+use crate::Error;
+use publication::Publication;
+use response::Response;
+use serde::Deserialize;
+use serde_json::Value;
+
+// Every frame Kraken can send us.
+// Object frames are dispatched on their `event` field and array frames on their `channelName`,
+// so each frame is only ever deserialized into the one type it claims to be.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Publication(Publication),
+    Response(Response),
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Malformed(serde_json::Error),
+    UnknownFrame,
+    UnknownEvent(String),
+    UnknownChannel(String),
+    // A response of another type than the request it answers, e.g. a `pong` to a `subscribe`.
+    UnexpectedResponse,
+    Invalid {
+        kind: &'static str,
+        source: serde_json::Error,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Malformed(e) => write!(f, "malformed frame: {}", e),
+            ParseError::UnknownFrame => {
+                write!(f, "frame is neither an event object nor a channel array")
+            }
+            ParseError::UnknownEvent(event) => write!(f, "unknown event `{}`", event),
+            ParseError::UnknownChannel(channel) => write!(f, "unknown channel `{}`", channel),
+            ParseError::UnexpectedResponse => write!(f, "unexpected response type"),
+            ParseError::Invalid { kind, source } => write!(f, "invalid {} frame: {}", kind, source),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Malformed(e) | ParseError::Invalid { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Deserializes the frame already parsed into a `Value`, borrowing its strings.
+fn parse<'a, T: Deserialize<'a>>(kind: &'static str, frame: &'a Value) -> Result<T, ParseError> {
+    T::deserialize(frame).map_err(|source| ParseError::Invalid { kind, source })
+}
+
+impl Message {
+    // Like `str::parse`, but without copying the frame into the error, for callers that already
+    // keep it, e.g. `ws::Event::Invalid`.
+    pub(crate) fn parse_frame(s: &str) -> Result<Self, ParseError> {
+        let frame = serde_json::from_str::<Value>(s).map_err(ParseError::Malformed)?;
+
+        match &frame {
+            Value::Object(object) => match object
+                .get("event")
+                .and_then(Value::as_str)
+                .ok_or(ParseError::UnknownFrame)?
+            {
+                "heartbeat" => parse("heartbeat", &frame)
+                    .map(|p| Message::Publication(Publication::Heartbeat(p))),
+                "systemStatus" => parse("systemStatus", &frame)
+                    .map(|p| Message::Publication(Publication::SystemStatus(p))),
+                "subscriptionStatus" => parse("subscriptionStatus", &frame)
+                    .map(|r| Message::Response(Response::SubscriptionStatus(r))),
+                "pong" => parse("pong", &frame).map(|r| Message::Response(Response::Pong(r))),
+                "error" => parse("error", &frame).map(|r| Message::Response(Response::Error(r))),
+                "addOrderStatus" => parse("addOrderStatus", &frame)
+                    .map(|r| Message::Response(Response::AddOrderStatus(r))),
+                "cancelOrderStatus" => parse("cancelOrderStatus", &frame)
+                    .map(|r| Message::Response(Response::CancelOrderStatus(r))),
+                "cancelAllStatus" => parse("cancelAllStatus", &frame)
+                    .map(|r| Message::Response(Response::CancelAllStatus(r))),
+                other => Err(ParseError::UnknownEvent(other.to_string())),
+            },
+            Value::Array(values) => {
+                // the channel name is always the second to last element
+                let channel_name = values
+                    .len()
+                    .checked_sub(2)
+                    .and_then(|idx| values[idx].as_str())
+                    .ok_or(ParseError::UnknownFrame)?;
+                let channel = channel_name.split('-').next().unwrap_or_default();
+
+                match channel {
+                    "ticker" => parse("ticker", &frame).map(Publication::Ticker),
+                    "ohlc" => parse("ohlc", &frame).map(Publication::Ohlc),
+                    "trade" => parse("trade", &frame).map(Publication::Trade),
+                    "spread" => parse("spread", &frame).map(Publication::Spread),
+                    "ownTrades" => parse("ownTrades", &frame).map(Publication::OwnTrades),
+                    "openOrders" => parse("openOrders", &frame).map(Publication::OpenOrders),
+                    "book" => {
+                        let is_snapshot = values
+                            .get(1)
+                            .and_then(|data| data.as_object())
+                            .map(|data| data.contains_key("as") || data.contains_key("bs"))
+                            .unwrap_or(false);
+                        if is_snapshot {
+                            parse("book snapshot", &frame).map(Publication::BookSnapshot)
+                        } else {
+                            parse("book update", &frame).map(Publication::BookUpdate)
+                        }
+                    }
+                    _ => Err(ParseError::UnknownChannel(channel_name.to_string())),
+                }
+                .map(Message::Publication)
+            }
+            _ => Err(ParseError::UnknownFrame),
+        }
+    }
+}
+
+impl std::str::FromStr for Message {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Message::parse_frame(s).map_err(|source| Error::protocol(s, source))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let payloads = vec![
+            r#"{"event":"heartbeat"}"#,
+            r#"{"connectionID":8628615390848610000,"event":"systemStatus","status":"online","version":"1.0.0"}"#,
+            r#"{"event":"pong","reqid":42}"#,
+            r#"{"channelName":"ticker","event":"subscriptionStatus","pair":"XBT/EUR","status":"subscribed","subscription":{"name":"ticker"},"channelID":10001}"#,
+            r#"[0,["5698.40000","5700.00000","1542057299.545897","1.01234567","0.98765432"],"spread","BTC/USD"]"#,
+            r#"[42,["1542057314.748456","1542057360.435743","3586.70000","3586.70000","3586.60000","3586.60000","3586.68894","0.03373000",2],"ohlc-5","BTC/USD"]"#,
+            r#"[0,{"as":[["5541.30000","2.50700000","1534614248.123678"]],"bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-100","BTC/USD"]"#,
+            r#"[1234,{"a":[["5541.30000","2.50700000","1534614248.456738"]]},{"b":[["5541.30000","0.00000000","1534614335.345903"]],"c":"974942666"},"book-10","XBT/USD"]"#,
+            r#"[[{"OGTT3Y-C6I3P-XRI6HX":{"status":"closed"}}],"openOrders",{"sequence":235}]"#,
+        ];
+
+        let messages = payloads
+            .into_iter()
+            .map(|payload| payload.parse::<Message>().unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            messages[0],
+            Message::Publication(Publication::Heartbeat(_))
+        ));
+        assert!(matches!(
+            messages[1],
+            Message::Publication(Publication::SystemStatus(_))
+        ));
+        assert!(matches!(messages[2], Message::Response(Response::Pong(_))));
+        assert!(matches!(
+            messages[3],
+            Message::Response(Response::SubscriptionStatus(_))
+        ));
+        assert!(matches!(
+            messages[4],
+            Message::Publication(Publication::Spread(_))
+        ));
+        assert!(matches!(
+            messages[5],
+            Message::Publication(Publication::Ohlc(_))
+        ));
+        assert!(matches!(
+            messages[6],
+            Message::Publication(Publication::BookSnapshot(_))
+        ));
+        assert!(matches!(
+            messages[7],
+            Message::Publication(Publication::BookUpdate(_))
+        ));
+        assert!(matches!(
+            messages[8],
+            Message::Publication(Publication::OpenOrders(_))
+        ));
+    }
+
+    #[test]
+    fn test_message_errors() {
+        assert!(matches!(
+            "{".parse::<Message>(),
+            Err(Error::Protocol {
+                source: ParseError::Malformed(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            "42".parse::<Message>(),
+            Err(Error::Protocol {
+                source: ParseError::UnknownFrame,
+                ..
+            })
+        ));
+        assert!(matches!(
+            r#"{"event":"unknown"}"#.parse::<Message>(),
+            Err(Error::Protocol {
+                source: ParseError::UnknownEvent(event),
+                ..
+            }) if event == "unknown"
+        ));
+        assert!(matches!(
+            r#"[0,{},"unknown","XBT/USD"]"#.parse::<Message>(),
+            Err(Error::Protocol {
+                source: ParseError::UnknownChannel(channel),
+                ..
+            }) if channel == "unknown"
+        ));
+        assert!(matches!(
+            r#"[0,["invalid"],"spread","XBT/USD"]"#.parse::<Message>(),
+            Err(Error::Protocol {
+                source: ParseError::Invalid { kind: "spread", .. },
+                ..
+            })
+        ));
+    }
+}
@@ -138,7 +138,7 @@ pub mod trade {
     use crate::core::{CurrencyPair, KrakenFloat};
     use serde::Deserialize;
 
-    #[derive(Debug, Deserialize, Clone)]
+    #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
     #[serde(try_from = "&str")]
     pub enum OrderSide {
         Buy,
@@ -146,7 +146,7 @@ pub mod trade {
     }
 
     impl std::convert::TryFrom<&str> for OrderSide {
-        type Error = &'static str;
+        type Error = crate::Error;
 
         fn try_from(value: &str) -> Result<Self, Self::Error> {
             if value == "s" {
@@ -154,12 +154,12 @@ pub mod trade {
             } else if value == "b" {
                 Ok(OrderSide::Buy)
             } else {
-                Err("invalid trade::OrderSide")
+                Err(crate::Error::parse(value, "invalid trade::OrderSide"))
             }
         }
     }
 
-    #[derive(Debug, Deserialize, Clone)]
+    #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
     #[serde(try_from = "&str")]
     pub enum OrderType {
         Market,
@@ -167,7 +167,7 @@ pub mod trade {
     }
 
     impl std::convert::TryFrom<&str> for OrderType {
-        type Error = &'static str;
+        type Error = crate::Error;
 
         fn try_from(value: &str) -> Result<Self, Self::Error> {
             if value == "m" {
@@ -175,12 +175,12 @@ pub mod trade {
             } else if value == "l" {
                 Ok(OrderType::Limit)
             } else {
-                Err("invalid trade::OrderSide")
+                Err(crate::Error::parse(value, "invalid trade::OrderType"))
             }
         }
     }
 
-    #[derive(Debug, Deserialize, Clone)]
+    #[derive(Debug, Deserialize, Clone, PartialEq)]
     pub struct Data<TimeRepr> {
         pub price: KrakenFloat,
         pub volume: KrakenFloat,
@@ -254,7 +254,7 @@ pub mod book {
         pub struct BookSnapshot {
             #[serde(rename = "channelID")]
             pub channel_id: i64,
-            data: Data,
+            pub data: Data,
             #[serde(rename = "channelName")]
             pub channel_name: String,
             pub pair: CurrencyPair,
@@ -279,19 +279,19 @@ pub mod book {
         #[derive(Debug, Deserialize, Clone)]
         pub struct AskUpdate {
             #[serde(rename = "a")]
-            updates: Vec<PriceLevelData>,
+            pub updates: Vec<PriceLevelData>,
 
             #[serde(rename = "c", default, skip_serializing_if = "Option::is_none")]
-            checksum: Option<String>,
+            pub checksum: Option<String>,
         }
 
         #[derive(Debug, Deserialize, Clone)]
         pub struct BidUpdate {
             #[serde(rename = "b")]
-            updates: Vec<PriceLevelData>,
+            pub updates: Vec<PriceLevelData>,
 
             #[serde(rename = "c", default, skip_serializing_if = "Option::is_none")]
-            checksum: Option<String>,
+            pub checksum: Option<String>,
         }
 
         #[derive(Debug, Deserialize, Clone)]
@@ -330,17 +330,17 @@ pub mod book {
         #[derive(Debug, Deserialize, Clone)]
         #[serde(from = "BookUpdateIntermdiate")]
         pub struct BookUpdate {
-            channel_id: i64,
+            pub channel_id: i64,
 
             #[serde(flatten, skip_serializing_if = "Option::is_none")]
-            ask: Option<AskUpdate>,
+            pub ask: Option<AskUpdate>,
 
             #[serde(flatten, skip_serializing_if = "Option::is_none")]
-            bid: Option<BidUpdate>,
+            pub bid: Option<BidUpdate>,
 
-            channel_name: String,
+            pub channel_name: String,
 
-            pair: CurrencyPair,
+            pub pair: CurrencyPair,
         }
 
         impl From<BookUpdateIntermdiate> for BookUpdate {
@@ -389,12 +389,176 @@ pub mod book {
     }
 }
 
+pub mod order {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Side {
+        Buy,
+        Sell,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum OrderType {
+        Market,
+        Limit,
+        StopLoss,
+        TakeProfit,
+        StopLossProfit,
+        StopLossProfitLimit,
+        StopLossLimit,
+        TakeProfitLimit,
+        TrailingStop,
+        TrailingStopLimit,
+        StopLossAndLimit,
+        SettlePosition,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Status {
+        Pending,
+        Open,
+        Closed,
+        Canceled,
+        Expired,
+    }
+} // mod order
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Sequence {
+    pub sequence: i64,
+}
+
+pub mod own_trades {
+    use super::{
+        order::{OrderType, Side},
+        Sequence,
+    };
+    use crate::core::{CurrencyPair, KrakenFloat};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        pub ordertxid: String,
+        pub postxid: String,
+        pub pair: CurrencyPair,
+        pub time: KrakenFloat,
+        #[serde(rename = "type")]
+        pub ty: Side,
+        pub ordertype: OrderType,
+        pub price: KrakenFloat,
+        pub cost: KrakenFloat,
+        pub fee: KrakenFloat,
+        pub vol: KrakenFloat,
+        pub margin: KrakenFloat,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct OwnTrades {
+        // each entry maps a trade id to its trade
+        pub data: Vec<HashMap<String, Data>>,
+        #[serde(rename = "channelName")]
+        pub channel_name: String,
+        pub sequence: Sequence,
+    }
+
+    impl OwnTrades {
+        pub fn trades(&self) -> impl Iterator<Item = (&String, &Data)> {
+            self.data.iter().flat_map(|trades| trades.iter())
+        }
+    }
+} // mod own_trades
+
+pub mod open_orders {
+    use super::{
+        order::{OrderType, Side, Status},
+        Sequence,
+    };
+    use crate::core::{CurrencyPair, KrakenFloat};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Descr {
+        pub pair: CurrencyPair,
+        #[serde(rename = "type")]
+        pub ty: Side,
+        pub ordertype: OrderType,
+        pub price: KrakenFloat,
+        pub price2: KrakenFloat,
+        pub leverage: String,
+        pub order: String,
+        pub close: String,
+    }
+
+    // The first message on the channel carries every field of every open order, subsequent
+    // messages only carry the fields that changed (e.g. `status`, `vol_exec`, `cost`).
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        #[serde(default)]
+        pub refid: Option<String>,
+        #[serde(default)]
+        pub userref: Option<i64>,
+        #[serde(default)]
+        pub status: Option<Status>,
+        #[serde(default)]
+        pub opentm: Option<KrakenFloat>,
+        #[serde(default)]
+        pub starttm: Option<KrakenFloat>,
+        #[serde(default)]
+        pub expiretm: Option<KrakenFloat>,
+        #[serde(default)]
+        pub descr: Option<Descr>,
+        #[serde(default)]
+        pub vol: Option<KrakenFloat>,
+        #[serde(default)]
+        pub vol_exec: Option<KrakenFloat>,
+        #[serde(default)]
+        pub cost: Option<KrakenFloat>,
+        #[serde(default)]
+        pub fee: Option<KrakenFloat>,
+        #[serde(default)]
+        pub avg_price: Option<KrakenFloat>,
+        #[serde(default)]
+        pub stopprice: Option<KrakenFloat>,
+        #[serde(default)]
+        pub limitprice: Option<KrakenFloat>,
+        #[serde(default)]
+        pub misc: Option<String>,
+        #[serde(default)]
+        pub oflags: Option<String>,
+        #[serde(default)]
+        pub cancel_reason: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct OpenOrders {
+        // each entry maps an order id to its (partial) state
+        pub data: Vec<HashMap<String, Data>>,
+        #[serde(rename = "channelName")]
+        pub channel_name: String,
+        pub sequence: Sequence,
+    }
+
+    impl OpenOrders {
+        pub fn orders(&self) -> impl Iterator<Item = (&String, &Data)> {
+            self.data.iter().flat_map(|orders| orders.iter())
+        }
+    }
+} // mod open_orders
+
 use serde::Deserialize;
 
 pub use book::snapshot::BookSnapshot;
 pub use book::update::BookUpdate;
 pub use hearbeat::Heartbeat;
 pub use ohlc::Ohlc;
+pub use open_orders::OpenOrders;
+pub use own_trades::OwnTrades;
 pub use spread::Spread;
 pub use system_status::SystemStatus;
 pub use ticker::Ticker;
@@ -411,6 +575,37 @@ pub enum Publication {
     Spread(Spread),
     BookSnapshot(BookSnapshot),
     BookUpdate(BookUpdate),
+    OwnTrades(OwnTrades),
+    OpenOrders(OpenOrders),
+}
+
+impl Publication {
+    // Only publications of public channels carry a channel id.
+    pub fn channel_id(&self) -> Option<i64> {
+        match self {
+            Publication::Ticker(p) => Some(p.channel_id),
+            Publication::Ohlc(p) => Some(p.channel_id),
+            Publication::Trade(p) => Some(p.channel_id),
+            Publication::Spread(p) => Some(p.channel_id),
+            Publication::BookSnapshot(p) => Some(p.channel_id),
+            Publication::BookUpdate(p) => Some(p.channel_id),
+            _ => None,
+        }
+    }
+
+    pub fn channel_name(&self) -> Option<&str> {
+        match self {
+            Publication::Ticker(p) => Some(&p.channel_name),
+            Publication::Ohlc(p) => Some(&p.channel_name),
+            Publication::Trade(p) => Some(&p.channel_name),
+            Publication::Spread(p) => Some(&p.channel_name),
+            Publication::BookSnapshot(p) => Some(&p.channel_name),
+            Publication::BookUpdate(p) => Some(&p.channel_name),
+            Publication::OwnTrades(p) => Some(&p.channel_name),
+            Publication::OpenOrders(p) => Some(&p.channel_name),
+            Publication::Heartbeat(_) | Publication::SystemStatus(_) => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -721,4 +916,136 @@ mod test {
             let _ = serde_json::from_str::<BookUpdate>(payload).unwrap();
         }
     }
+
+    #[test]
+    fn test_own_trades() {
+        let own_trades: OwnTrades = serde_json::from_str(
+            r#"
+        [
+            [
+                {
+                    "TDLH43-DVQXD-2KHVYY": {
+                        "cost": "1000000.00000",
+                        "fee": "1600.00000",
+                        "margin": "0.00000",
+                        "ordertxid": "TDLH43-DVQXD-2KHVYY",
+                        "ordertype": "limit",
+                        "pair": "XBT/EUR",
+                        "postxid": "OGTT3Y-C6I3P-XRI6HX",
+                        "price": "100000.00000",
+                        "time": "1560516023.070651",
+                        "type": "sell",
+                        "vol": "1000000000.00000000"
+                    }
+                },
+                {
+                    "TDLH43-DVQXD-2KHVYY": {
+                        "cost": "1000000.00000",
+                        "fee": "600.00000",
+                        "margin": "0.00000",
+                        "ordertxid": "TDLH43-DVQXD-2KHVYY",
+                        "ordertype": "market",
+                        "pair": "XBT/EUR",
+                        "postxid": "OGTT3Y-C6I3P-XRI6HX",
+                        "price": "100000.00000",
+                        "time": "1560516023.070658",
+                        "type": "buy",
+                        "vol": "1000000000.00000000"
+                    }
+                }
+            ],
+            "ownTrades",
+            {
+                "sequence": 2948
+            }
+        ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(own_trades.sequence.sequence, 2948);
+        let trades = own_trades.trades().collect::<Vec<_>>();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].0, "TDLH43-DVQXD-2KHVYY");
+        assert_eq!(trades[0].1.ty, order::Side::Sell);
+        assert_eq!(trades[1].1.ordertype, order::OrderType::Market);
+    }
+
+    #[test]
+    fn test_open_orders() {
+        let open_orders: OpenOrders = serde_json::from_str(
+            r#"
+        [
+            [
+                {
+                    "OGTT3Y-C6I3P-XRI6HX": {
+                        "avg_price": "34.50000",
+                        "cost": "0.00000",
+                        "descr": {
+                            "close": "",
+                            "leverage": "0:1",
+                            "order": "sell 10.00345345 XBT/EUR @ limit 34.50000 with 0:1 leverage",
+                            "ordertype": "limit",
+                            "pair": "XBT/EUR",
+                            "price": "34.50000",
+                            "price2": "0.00000",
+                            "type": "sell"
+                        },
+                        "expiretm": "0.000000",
+                        "fee": "0.00000",
+                        "limitprice": "34.50000",
+                        "misc": "",
+                        "oflags": "fcib",
+                        "opentm": "0.000000",
+                        "refid": "OKIVMP-5GVZN-Z2D2UA",
+                        "starttm": "0.000000",
+                        "status": "open",
+                        "stopprice": "0.000000",
+                        "userref": 0,
+                        "vol": "10.00345345",
+                        "vol_exec": "0.00000000"
+                    }
+                }
+            ],
+            "openOrders",
+            {
+                "sequence": 234
+            }
+        ]
+            "#,
+        )
+        .unwrap();
+
+        let (txid, data) = open_orders.orders().next().unwrap();
+        assert_eq!(txid, "OGTT3Y-C6I3P-XRI6HX");
+        assert_eq!(data.status, Some(order::Status::Open));
+        assert_eq!(data.descr.as_ref().unwrap().ty, order::Side::Sell);
+
+        let update: OpenOrders = serde_json::from_str(
+            r#"
+        [
+            [
+                {
+                    "OGTT3Y-C6I3P-XRI6HX": {
+                        "status": "closed",
+                        "cost": "345.03450",
+                        "vol_exec": "10.00345345",
+                        "fee": "0.55205",
+                        "avg_price": "34.50000"
+                    }
+                }
+            ],
+            "openOrders",
+            {
+                "sequence": 235
+            }
+        ]
+            "#,
+        )
+        .unwrap();
+
+        let (_, data) = update.orders().next().unwrap();
+        assert_eq!(data.status, Some(order::Status::Closed));
+        assert!(data.descr.is_none());
+    }
 }
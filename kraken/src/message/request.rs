@@ -1,18 +1,13 @@
 pub mod ping {
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum PingEvent {
+        #[default]
         Ping,
     }
 
-    impl Default for PingEvent {
-        fn default() -> Self {
-            PingEvent::Ping
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize, Clone, TypedBuilder)]
     #[serde(deny_unknown_fields)]
     pub struct Ping {
@@ -27,33 +22,28 @@ pub mod ping {
 
 pub mod subscribe {
     use crate::core::CurrencyPair;
+    use crate::message::channel::{ChannelName, Depth, Interval};
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum SubscribeEvent {
+        #[default]
         Subscribe,
     }
 
-    impl Default for SubscribeEvent {
-        fn default() -> Self {
-            SubscribeEvent::Subscribe
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize, Clone, TypedBuilder)]
     #[serde(deny_unknown_fields)]
     pub struct Subscription {
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
-        pub depth: Option<i64>,
+        pub depth: Option<Depth>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
-        pub interval: Option<i64>,
+        pub interval: Option<Interval>,
 
-        #[builder(setter(into))]
-        pub name: String,
+        pub name: ChannelName,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
@@ -85,33 +75,28 @@ pub mod subscribe {
 
 pub mod unsubscribe {
     use crate::core::CurrencyPair;
+    use crate::message::channel::{ChannelName, Depth, Interval};
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum UnsubscribeEvent {
+        #[default]
         Unsubscribe,
     }
 
-    impl Default for UnsubscribeEvent {
-        fn default() -> Self {
-            UnsubscribeEvent::Unsubscribe
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize, Clone, TypedBuilder)]
     #[serde(deny_unknown_fields)]
     pub struct Subscription {
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
-        pub depth: Option<i64>,
+        pub depth: Option<Depth>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
-        pub interval: Option<i64>,
+        pub interval: Option<Interval>,
 
-        #[builder(setter(into))]
-        pub name: String,
+        pub name: ChannelName,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
@@ -121,7 +106,7 @@ pub mod unsubscribe {
     #[derive(Debug, Deserialize, Serialize, Clone)]
     pub enum UnsubscribeFrom {
         #[serde(rename = "channelID")]
-        ChannelID(i32),
+        ChannelID(i64),
 
         #[serde(rename = "pair")]
         Pair(Vec<CurrencyPair>),
@@ -149,17 +134,365 @@ pub mod unsubscribe {
 
 pub mod add_order {
     use crate::core::{CurrencyPair, KrakenFloat};
+    use crate::message::publication::order::OrderType as OrderKind;
+    use crate::rest_api::response::asset_pairs::AssetPairInfo;
+    use crate::Error;
     use serde::{Deserialize, Serialize};
+    use std::collections::BTreeSet;
+    use std::convert::TryFrom;
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    pub use crate::message::publication::order::Side;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum AddOrderEvent {
+        #[default]
         AddOrder,
     }
 
-    impl Default for AddOrderEvent {
-        fn default() -> Self {
-            AddOrderEvent::AddOrder
+    // The order type together with the prices it requires, so e.g. a limit order can't be built
+    // without its limit price.
+    // For the `*-limit` types `price` is the trigger price and `price2` the limit price.
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+    #[serde(try_from = "OrderTypeRepr", into = "OrderTypeRepr")]
+    pub enum OrderType {
+        Market,
+        Limit {
+            price: KrakenFloat,
+        },
+        StopLoss {
+            price: KrakenFloat,
+        },
+        TakeProfit {
+            price: KrakenFloat,
+        },
+        StopLossLimit {
+            price: KrakenFloat,
+            price2: KrakenFloat,
+        },
+        TakeProfitLimit {
+            price: KrakenFloat,
+            price2: KrakenFloat,
+        },
+        SettlePosition,
+    }
+
+    impl OrderType {
+        pub fn kind(&self) -> OrderKind {
+            match self {
+                OrderType::Market => OrderKind::Market,
+                OrderType::Limit { .. } => OrderKind::Limit,
+                OrderType::StopLoss { .. } => OrderKind::StopLoss,
+                OrderType::TakeProfit { .. } => OrderKind::TakeProfit,
+                OrderType::StopLossLimit { .. } => OrderKind::StopLossLimit,
+                OrderType::TakeProfitLimit { .. } => OrderKind::TakeProfitLimit,
+                OrderType::SettlePosition => OrderKind::SettlePosition,
+            }
+        }
+
+        pub fn price(&self) -> Option<KrakenFloat> {
+            match self {
+                OrderType::Market | OrderType::SettlePosition => None,
+                OrderType::Limit { price }
+                | OrderType::StopLoss { price }
+                | OrderType::TakeProfit { price }
+                | OrderType::StopLossLimit { price, .. }
+                | OrderType::TakeProfitLimit { price, .. } => Some(*price),
+            }
+        }
+
+        pub fn price2(&self) -> Option<KrakenFloat> {
+            match self {
+                OrderType::StopLossLimit { price2, .. }
+                | OrderType::TakeProfitLimit { price2, .. } => Some(*price2),
+                _ => None,
+            }
+        }
+
+        fn from_parts(
+            kind: OrderKind,
+            price: Option<KrakenFloat>,
+            price2: Option<KrakenFloat>,
+        ) -> Result<Self, Error> {
+            let invalid = |reason| Error::parse(format!("{:?}", kind), reason);
+            match (kind, price, price2) {
+                (OrderKind::Market, None, None) => Ok(OrderType::Market),
+                (OrderKind::SettlePosition, None, None) => Ok(OrderType::SettlePosition),
+                (OrderKind::Limit, Some(price), None) => Ok(OrderType::Limit { price }),
+                (OrderKind::StopLoss, Some(price), None) => Ok(OrderType::StopLoss { price }),
+                (OrderKind::TakeProfit, Some(price), None) => Ok(OrderType::TakeProfit { price }),
+                (OrderKind::StopLossLimit, Some(price), Some(price2)) => {
+                    Ok(OrderType::StopLossLimit { price, price2 })
+                }
+                (OrderKind::TakeProfitLimit, Some(price), Some(price2)) => {
+                    Ok(OrderType::TakeProfitLimit { price, price2 })
+                }
+                (OrderKind::Market, _, _)
+                | (OrderKind::SettlePosition, _, _)
+                | (OrderKind::Limit, _, _)
+                | (OrderKind::StopLoss, _, _)
+                | (OrderKind::TakeProfit, _, _)
+                | (OrderKind::StopLossLimit, _, _)
+                | (OrderKind::TakeProfitLimit, _, _) => {
+                    Err(invalid("prices don't match the add_order::OrderType"))
+                }
+                _ => Err(invalid("unsupported add_order::OrderType")),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Serialize)]
+    struct OrderTypeRepr {
+        ordertype: OrderKind,
+
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        price: Option<KrakenFloat>,
+
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        price2: Option<KrakenFloat>,
+    }
+
+    impl TryFrom<OrderTypeRepr> for OrderType {
+        type Error = Error;
+
+        fn try_from(repr: OrderTypeRepr) -> Result<Self, Self::Error> {
+            OrderType::from_parts(repr.ordertype, repr.price, repr.price2)
+        }
+    }
+
+    impl From<OrderType> for OrderTypeRepr {
+        fn from(ordertype: OrderType) -> Self {
+            Self {
+                ordertype: ordertype.kind(),
+                price: ordertype.price(),
+                price2: ordertype.price2(),
+            }
+        }
+    }
+
+    // The conditional close order placed once the order fills.
+    // Only limit, stop-loss and take-profit types (and their `*-limit` variants) are allowed.
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+    #[serde(try_from = "CloseRepr", into = "CloseRepr")]
+    pub struct Close(OrderType);
+
+    impl Close {
+        pub fn ordertype(&self) -> &OrderType {
+            &self.0
+        }
+    }
+
+    impl TryFrom<OrderType> for Close {
+        type Error = Error;
+
+        fn try_from(ordertype: OrderType) -> Result<Self, Self::Error> {
+            match ordertype {
+                OrderType::Market | OrderType::SettlePosition => Err(Error::parse(
+                    format!("{:?}", ordertype),
+                    "invalid add_order::Close order type",
+                )),
+                _ => Ok(Close(ordertype)),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Serialize)]
+    struct CloseRepr {
+        #[serde(rename = "close[ordertype]")]
+        ordertype: OrderKind,
+
+        #[serde(
+            rename = "close[price]",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        price: Option<KrakenFloat>,
+
+        #[serde(
+            rename = "close[price2]",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        price2: Option<KrakenFloat>,
+    }
+
+    impl TryFrom<CloseRepr> for Close {
+        type Error = Error;
+
+        fn try_from(repr: CloseRepr) -> Result<Self, Self::Error> {
+            Close::try_from(OrderType::from_parts(
+                repr.ordertype,
+                repr.price,
+                repr.price2,
+            )?)
+        }
+    }
+
+    impl From<Close> for CloseRepr {
+        fn from(close: Close) -> Self {
+            Self {
+                ordertype: close.0.kind(),
+                price: close.0.price(),
+                price2: close.0.price2(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum OrderFlag {
+        // volume in quote currency
+        Viqc,
+        // prefer fee in base currency
+        Fcib,
+        // prefer fee in quote currency
+        Fciq,
+        // no market price protection
+        Nompp,
+        // post only
+        Post,
+    }
+
+    impl OrderFlag {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                OrderFlag::Viqc => "viqc",
+                OrderFlag::Fcib => "fcib",
+                OrderFlag::Fciq => "fciq",
+                OrderFlag::Nompp => "nompp",
+                OrderFlag::Post => "post",
+            }
+        }
+    }
+
+    impl std::str::FromStr for OrderFlag {
+        type Err = Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "viqc" => Ok(OrderFlag::Viqc),
+                "fcib" => Ok(OrderFlag::Fcib),
+                "fciq" => Ok(OrderFlag::Fciq),
+                "nompp" => Ok(OrderFlag::Nompp),
+                "post" => Ok(OrderFlag::Post),
+                _ => Err(Error::parse(s, "invalid add_order::OrderFlag")),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
+    #[serde(try_from = "&str", into = "String")]
+    pub struct OrderFlags(BTreeSet<OrderFlag>);
+
+    impl OrderFlags {
+        pub fn new<I: IntoIterator<Item = OrderFlag>>(flags: I) -> Result<Self, Error> {
+            let flags = Self(flags.into_iter().collect());
+            if flags.contains(OrderFlag::Fcib) && flags.contains(OrderFlag::Fciq) {
+                return Err(Error::parse(
+                    String::from(flags),
+                    "fcib and fciq are mutually exclusive",
+                ));
+            }
+            Ok(flags)
+        }
+
+        pub fn contains(&self, flag: OrderFlag) -> bool {
+            self.0.contains(&flag)
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = OrderFlag> + '_ {
+            self.0.iter().copied()
+        }
+    }
+
+    impl TryFrom<&str> for OrderFlags {
+        type Error = Error;
+
+        fn try_from(value: &str) -> Result<Self, Self::Error> {
+            OrderFlags::new(
+                value
+                    .split(',')
+                    .map(str::parse::<OrderFlag>)
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        }
+    }
+
+    impl From<OrderFlags> for String {
+        fn from(flags: OrderFlags) -> Self {
+            flags
+                .iter()
+                .map(|flag| flag.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+    }
+
+    // Scheduled start/expire time: now, an absolute unix time, or an offset from now.
+    // Offsets are sent in whole seconds, rounded up.
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+    #[serde(try_from = "&str", into = "String")]
+    pub enum OrderTime {
+        Now,
+        At(chrono::DateTime<chrono::Utc>),
+        After(std::time::Duration),
+    }
+
+    impl TryFrom<&str> for OrderTime {
+        type Error = Error;
+
+        fn try_from(value: &str) -> Result<Self, Self::Error> {
+            let invalid = || Error::parse(value, "invalid add_order::OrderTime");
+            if value == "0" {
+                Ok(OrderTime::Now)
+            } else if let Some(offset) = value.strip_prefix('+') {
+                match offset.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 && !offset.starts_with('+') => {
+                        Ok(OrderTime::After(std::time::Duration::from_secs(seconds)))
+                    }
+                    _ => Err(invalid()),
+                }
+            } else {
+                let timestamp = value.parse::<i64>().map_err(|_| invalid())?;
+                chrono::DateTime::from_timestamp(timestamp, 0)
+                    .map(OrderTime::At)
+                    .ok_or_else(invalid)
+            }
+        }
+    }
+
+    impl From<OrderTime> for String {
+        fn from(time: OrderTime) -> Self {
+            match time {
+                OrderTime::Now => "0".to_string(),
+                OrderTime::At(dt) => dt.timestamp().to_string(),
+                OrderTime::After(offset) => {
+                    let seconds = offset.as_secs() + u64::from(offset.subsec_nanos() > 0);
+                    format!("+{}", seconds)
+                }
+            }
+        }
+    }
+
+    // The WebSocket API sends `userref` as a string.
+    mod userref {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            userref: &Option<i32>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match userref {
+                Some(userref) => serializer.serialize_str(&userref.to_string()),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<i32>, D::Error> {
+            Option::<&str>::deserialize(deserializer)?
+                .map(|userref| userref.parse::<i32>().map_err(serde::de::Error::custom))
+                .transpose()
         }
     }
 
@@ -169,30 +502,22 @@ pub mod add_order {
         #[builder(setter(skip), default)]
         event: AddOrderEvent,
 
-        #[builder(setter(into))]
-        pub token: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[builder(setter(into, strip_option), default)]
+        pub token: Option<String>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
         pub reqid: Option<i64>,
 
-        #[builder(setter(into))]
-        pub ordertype: String, // TODO CUSTOM TYPE
+        #[serde(flatten)]
+        pub ordertype: OrderType,
 
         #[serde(rename = "type")]
-        #[builder(setter(into))]
-        pub ty: String, // TODO CUSTOM TYPE
+        pub ty: Side,
 
         pub pair: CurrencyPair,
 
-        #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub price: Option<KrakenFloat>,
-
-        #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub price2: Option<KrakenFloat>,
-
         #[builder(setter(into))]
         pub volume: KrakenFloat,
 
@@ -201,66 +526,183 @@ pub mod add_order {
         pub leverage: Option<KrakenFloat>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub oflags: Option<String>, // TODO CUSTOM TYPE
+        #[builder(setter(strip_option), default)]
+        pub oflags: Option<OrderFlags>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub starttm: Option<String>, // TODO CUSTOM TYPE
+        #[builder(setter(strip_option), default)]
+        pub starttm: Option<OrderTime>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub expiretm: Option<String>, // TODO CUSTOM TYPE
+        #[builder(setter(strip_option), default)]
+        pub expiretm: Option<OrderTime>,
 
-        #[serde(skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub userref: Option<String>, // TODO CUSTOM TYPE
+        #[serde(skip_serializing_if = "Option::is_none", with = "userref", default)]
+        #[builder(setter(strip_option), default)]
+        pub userref: Option<i32>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
         pub validate: Option<String>,
 
-        #[serde(rename = "close[ordertype]", skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub close_ordertype: Option<String>,
-
-        #[serde(rename = "close[price]", skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub close_price: Option<String>,
-
-        #[serde(rename = "close[price2]", skip_serializing_if = "Option::is_none")]
-        #[builder(setter(into, strip_option), default)]
-        pub close_price2: Option<String>,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        #[builder(setter(strip_option), default)]
+        pub close: Option<Close>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
         pub trading_agreement: Option<String>,
     }
+
+    // A trading rule of the pair an `AddOrder` breaks, see `AddOrder::violations`.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Violation {
+        // The trading rules are of another pair, so the order wasn't checked any further.
+        WrongPair {
+            pair: CurrencyPair,
+            altname: String,
+        },
+        VolumeBelowMinimum {
+            volume: KrakenFloat,
+            ordermin: KrakenFloat,
+        },
+        VolumeDecimals {
+            volume: KrakenFloat,
+            decimals: u32,
+        },
+        PriceDecimals {
+            price: KrakenFloat,
+            decimals: u32,
+        },
+        Leverage {
+            leverage: KrakenFloat,
+            allowed: BTreeSet<u32>,
+        },
+    }
+
+    impl std::fmt::Display for Violation {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Violation::WrongPair { pair, altname } => {
+                    write!(
+                        f,
+                        "order for {} checked against the rules of {}",
+                        pair, altname
+                    )
+                }
+                Violation::VolumeBelowMinimum { volume, ordermin } => {
+                    write!(f, "volume {} is below the minimum of {}", volume, ordermin)
+                }
+                Violation::VolumeDecimals { volume, decimals } => {
+                    write!(f, "volume {} has more than {} decimals", volume, decimals)
+                }
+                Violation::PriceDecimals { price, decimals } => {
+                    write!(f, "price {} has more than {} decimals", price, decimals)
+                }
+                Violation::Leverage { leverage, allowed } => write!(
+                    f,
+                    "leverage {} is not one of {:?}",
+                    leverage,
+                    allowed.iter().collect::<Vec<_>>()
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for Violation {}
+
+    impl AddOrder {
+        // Checks the order against the trading rules of its pair, returning every rule it breaks.
+        // `viqc` orders are sized in the quote currency, so their volume isn't checked against
+        // the base currency's `ordermin` and `lot_decimals`.
+        pub fn violations(&self, info: &AssetPairInfo) -> Vec<Violation> {
+            // pairs without a WebSocket name, e.g. dark pools, can't be traded here anyway
+            let same_pair = info
+                .wsname
+                .as_ref()
+                .and_then(|wsname| wsname.parse::<CurrencyPair>().ok())
+                .is_some_and(|pair| pair == self.pair);
+            if !same_pair {
+                return vec![Violation::WrongPair {
+                    pair: self.pair.clone(),
+                    altname: info.altname.clone(),
+                }];
+            }
+
+            let mut violations = Vec::new();
+
+            let viqc = self
+                .oflags
+                .as_ref()
+                .is_some_and(|oflags| oflags.contains(OrderFlag::Viqc));
+            if !viqc {
+                if self.volume < info.ordermin {
+                    violations.push(Violation::VolumeBelowMinimum {
+                        volume: self.volume,
+                        ordermin: info.ordermin,
+                    });
+                }
+                if !info.lot_decimals.is_valid(self.volume) {
+                    violations.push(Violation::VolumeDecimals {
+                        volume: self.volume,
+                        decimals: info.lot_decimals.decimals,
+                    });
+                }
+            }
+
+            let close = self.close.as_ref().map(Close::ordertype);
+            let prices = [
+                self.ordertype.price(),
+                self.ordertype.price2(),
+                close.and_then(OrderType::price),
+                close.and_then(OrderType::price2),
+            ];
+            for price in prices.iter().flatten() {
+                if !info.pair_decimals.is_valid(*price) {
+                    violations.push(Violation::PriceDecimals {
+                        price: *price,
+                        decimals: info.pair_decimals.decimals,
+                    });
+                }
+            }
+
+            if let Some(leverage) = self.leverage {
+                let allowed = match self.ty {
+                    Side::Buy => &info.leverage_buy,
+                    Side::Sell => &info.leverage_sell,
+                };
+                if !allowed.iter().any(|l| KrakenFloat::from(*l) == leverage) {
+                    violations.push(Violation::Leverage {
+                        leverage,
+                        allowed: allowed.clone(),
+                    });
+                }
+            }
+
+            violations
+        }
+    }
 }
 
 pub mod cancel_order {
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum CancelOrderEvent {
+        #[default]
         CancelOrder,
     }
 
-    impl Default for CancelOrderEvent {
-        fn default() -> Self {
-            CancelOrderEvent::CancelOrder
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize, Clone, TypedBuilder)]
     #[serde(deny_unknown_fields)]
     pub struct CancelOrder {
         #[builder(setter(skip), default)]
         event: CancelOrderEvent,
 
-        #[builder(setter(into))]
-        pub token: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[builder(setter(into, strip_option), default)]
+        pub token: Option<String>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
@@ -274,26 +716,22 @@ pub mod cancel_order {
 pub mod cancel_all {
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
     #[serde(rename_all = "camelCase")]
     enum CancelAllEvent {
+        #[default]
         CancelAll,
     }
 
-    impl Default for CancelAllEvent {
-        fn default() -> Self {
-            CancelAllEvent::CancelAll
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize, Clone, TypedBuilder)]
     #[serde(deny_unknown_fields)]
     pub struct CancelAll {
         #[builder(setter(skip), default)]
         event: CancelAllEvent,
 
-        #[builder(setter(into))]
-        pub token: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[builder(setter(into, strip_option), default)]
+        pub token: Option<String>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         #[builder(setter(into, strip_option), default)]
@@ -308,18 +746,159 @@ pub use ping::Ping;
 pub use subscribe::Subscribe;
 pub use unsubscribe::Unsubscribe;
 
-pub trait Request {}
-impl Request for Ping {}
-impl Request for Subscribe {}
-impl Request for Unsubscribe {}
-impl Request for AddOrder {}
-impl Request for CancelOrder {}
-impl Request for CancelAll {}
+use super::response::{self, Response};
+use crate::rate_limit::OrderAction;
+
+pub trait Request: serde::Serialize {
+    // The status Kraken answers this request with.
+    type Response;
+
+    fn reqid(&mut self) -> &mut Option<i64>;
+
+    // The token slot of a private request, left empty so the client can fill it in.
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        None
+    }
+
+    // What the request counts against the trading rate limit, if anything.
+    fn order(&self) -> Option<OrderAction> {
+        None
+    }
+
+    // Picks this request's status out of a response echoing its `reqid`.
+    fn response(response: Response) -> Option<Self::Response>;
+}
+
+impl Request for Ping {
+    type Response = response::Pong;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::Pong(pong) => Some(pong),
+            _ => None,
+        }
+    }
+}
+
+impl Request for Subscribe {
+    type Response = response::SubscriptionStatus;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        if self.subscription.name.is_private() {
+            Some(&mut self.subscription.token)
+        } else {
+            None
+        }
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::SubscriptionStatus(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl Request for Unsubscribe {
+    type Response = response::SubscriptionStatus;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        match &mut self.subscription {
+            Some(subscription) if subscription.name.is_private() => Some(&mut subscription.token),
+            _ => None,
+        }
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::SubscriptionStatus(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl Request for AddOrder {
+    type Response = response::AddOrderStatus;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.token)
+    }
+
+    fn order(&self) -> Option<OrderAction> {
+        Some(OrderAction::Add(self.pair.clone()))
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::AddOrderStatus(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl Request for CancelOrder {
+    type Response = response::CancelOrderStatus;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.token)
+    }
+
+    fn order(&self) -> Option<OrderAction> {
+        Some(OrderAction::Cancel(self.txid.clone()))
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::CancelOrderStatus(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl Request for CancelAll {
+    type Response = response::CancelAllStatus;
+
+    fn reqid(&mut self) -> &mut Option<i64> {
+        &mut self.reqid
+    }
+
+    fn token(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.token)
+    }
+
+    fn response(response: Response) -> Option<Self::Response> {
+        match response {
+            Response::CancelAllStatus(status) => Some(status),
+            _ => None,
+        }
+    }
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::core::CurrencyPair;
+    use crate::core::{CurrencyPair, KrakenFloat};
+    use crate::message::channel::{ChannelName, Depth, Interval};
     use json::object;
 
     #[test]
@@ -351,9 +930,9 @@ mod test {
     #[test]
     fn test_subscribe() {
         let subscription = subscribe::Subscription::builder()
-            .depth(1)
-            .interval(1)
-            .name("ticker")
+            .depth(Depth::D10)
+            .interval(Interval::OneHour)
+            .name(ChannelName::Ohlc)
             .snapshot(false)
             .token("0000")
             .build();
@@ -371,9 +950,9 @@ mod test {
                 "BTC/USD"
             ],
             subscription: {
-                depth: 1,
-                interval: 1,
-                name: "ticker",
+                depth: 10,
+                interval: 60,
+                name: "ohlc",
                 snapshot: false,
                 token: "0000"
             }
@@ -415,14 +994,23 @@ mod test {
             let json = serde_json::to_string(&value).unwrap();
             assert_eq!(json::parse(payload).unwrap(), json::parse(&json).unwrap());
         }
+
+        let invalid = vec![
+            r#"{"event":"subscribe","subscription":{"name":"trades"}}"#,
+            r#"{"event":"subscribe","subscription":{"interval":2,"name":"ohlc"}}"#,
+            r#"{"event":"subscribe","subscription":{"depth":42,"name":"book"}}"#,
+        ];
+        for payload in invalid {
+            assert!(serde_json::from_str::<Subscribe>(payload).is_err());
+        }
     }
 
     #[test]
     fn test_unsubscribe() {
         let subscription = unsubscribe::Subscription::builder()
-            .depth(1)
-            .interval(1)
-            .name("ticker")
+            .depth(Depth::D10)
+            .interval(Interval::OneHour)
+            .name(ChannelName::Ohlc)
             .token("0000")
             .build();
 
@@ -441,9 +1029,9 @@ mod test {
                 "BTC/USD"
             ],
             subscription: {
-                depth: 1,
-                interval: 1,
-                name: "ticker",
+                depth: 10,
+                interval: 60,
+                name: "ohlc",
                 token: "0000"
             }
         }
@@ -482,24 +1070,26 @@ mod test {
 
     #[test]
     fn test_add_order() {
+        use add_order::{Close, OrderFlag, OrderFlags, OrderTime, OrderType, Side};
+        use std::convert::TryFrom;
+
         let add_order = AddOrder::builder()
             .token("0000")
             .reqid(1)
-            .ordertype("a")
-            .ty("a")
+            .ordertype(OrderType::StopLossLimit {
+                price: 1.into(),
+                price2: 2.into(),
+            })
+            .ty(Side::Buy)
             .pair("BTC/USD".parse::<CurrencyPair>().unwrap())
-            .price(1.0)
-            .price2(1.0)
-            .volume(1.0)
-            .leverage(1.0)
-            .oflags("a")
-            .starttm("a")
-            .expiretm("a")
-            .userref("a")
+            .volume(1)
+            .leverage(1)
+            .oflags(OrderFlags::new(vec![OrderFlag::Post, OrderFlag::Fcib]).unwrap())
+            .starttm(OrderTime::Now)
+            .expiretm(OrderTime::After(std::time::Duration::from_secs(60)))
+            .userref(42)
             .validate("a")
-            .close_ordertype("a")
-            .close_price("a")
-            .close_price2("a")
+            .close(Close::try_from(OrderType::Limit { price: 3.into() }).unwrap())
             .trading_agreement("a")
             .build();
 
@@ -507,25 +1097,26 @@ mod test {
             event: "addOrder",
             token: "0000",
             reqid: 1,
-            ordertype: "a",
-            "type": "a",
-            pair: "BTC/USD",
+            ordertype: "stop-loss-limit",
             price: "1",
-            price2: "1",
+            price2: "2",
+            "type": "buy",
+            pair: "BTC/USD",
             volume: "1",
             leverage: "1",
-            oflags: "a",
-            starttm: "a",
-            expiretm: "a",
-            userref: "a",
+            oflags: "fcib,post",
+            starttm: "0",
+            expiretm: "+60",
+            userref: "42",
             validate: "a",
-            "close[ordertype]": "a",
-            "close[price]": "a",
-            "close[price2]": "a",
+            "close[ordertype]": "limit",
+            "close[price]": "3",
             trading_agreement: "a"
-        }
-        .to_string();
-        assert_eq!(serde_json::to_string(&add_order).unwrap(), json);
+        };
+        assert_eq!(
+            json::parse(&serde_json::to_string(&add_order).unwrap()).unwrap(),
+            json
+        );
 
         let payloads = vec![
             r#"{
@@ -548,12 +1139,156 @@ mod test {
                 "type": "buy",
                 "volume": "10"
               }"#,
+            r#"{
+                "event": "addOrder",
+                "ordertype": "market",
+                "pair": "XBT/USD",
+                "type": "sell",
+                "volume": "10",
+                "oflags": "viqc",
+                "starttm": "1600000000",
+                "userref": "-7"
+              }"#,
         ];
         for payload in payloads {
             let value: AddOrder = serde_json::from_str(payload).unwrap();
             let json = serde_json::to_string(&value).unwrap();
             assert_eq!(json::parse(payload).unwrap(), json::parse(&json).unwrap());
         }
+
+        let invalid = vec![
+            // limit order without a price
+            r#"{"event":"addOrder","ordertype":"limit","pair":"XBT/USD","type":"buy","volume":"10"}"#,
+            // market order with a price
+            r#"{"event":"addOrder","ordertype":"market","price":"1","pair":"XBT/USD","type":"buy","volume":"10"}"#,
+            // conflicting fee currencies
+            r#"{"event":"addOrder","ordertype":"market","pair":"XBT/USD","type":"buy","volume":"10","oflags":"fcib,fciq"}"#,
+            // unknown side
+            r#"{"event":"addOrder","ordertype":"market","pair":"XBT/USD","type":"a","volume":"10"}"#,
+            // unknown field
+            r#"{"event":"addOrder","ordertype":"market","pair":"XBT/USD","type":"buy","volume":"10","a":"1"}"#,
+            // non-positive offsets
+            r#"{"event":"addOrder","ordertype":"market","pair":"XBT/USD","type":"buy","volume":"10","expiretm":"+-60"}"#,
+            r#"{"event":"addOrder","ordertype":"market","pair":"XBT/USD","type":"buy","volume":"10","expiretm":"+0"}"#,
+        ];
+        for payload in invalid {
+            assert!(serde_json::from_str::<AddOrder>(payload).is_err());
+        }
+
+        assert!(Close::try_from(OrderType::Market).is_err());
+        assert_eq!(
+            String::from(OrderTime::After(std::time::Duration::from_millis(1500))),
+            "+2"
+        );
+        assert!(OrderFlags::new(vec![OrderFlag::Fcib, OrderFlag::Fciq]).is_err());
+    }
+
+    #[test]
+    fn test_add_order_violations() {
+        use crate::rest_api::response::asset_pairs::AssetPairInfo;
+        use add_order::{Close, OrderFlag, OrderFlags, OrderType, Side, Violation};
+        use std::convert::TryFrom;
+
+        let info: AssetPairInfo = serde_json::from_str(
+            r#"{
+                "altname": "XBTUSD",
+                "wsname": "XBT/USD",
+                "aclass_base": "currency",
+                "base": "XXBT",
+                "aclass_quote": "currency",
+                "quote": "ZUSD",
+                "lot": "unit",
+                "pair_decimals": 1,
+                "lot_decimals": 8,
+                "lot_multiplier": 1,
+                "leverage_buy": [2, 3],
+                "leverage_sell": [2],
+                "fees": [[0, 0.26]],
+                "fee_volume_currency": "ZUSD",
+                "margin_call": 80,
+                "margin_stop": 40,
+                "ordermin": "0.0001"
+            }"#,
+        )
+        .unwrap();
+        let pair = "XBT/USD".parse::<CurrencyPair>().unwrap();
+
+        let valid = AddOrder::builder()
+            .ordertype(OrderType::Limit {
+                price: "9000.5".parse().unwrap(),
+            })
+            .ty(Side::Buy)
+            .pair(pair.clone())
+            .volume("0.5".parse::<KrakenFloat>().unwrap())
+            .leverage(3)
+            .build();
+        assert!(valid.violations(&info).is_empty());
+
+        let invalid = AddOrder::builder()
+            .ordertype(OrderType::StopLossLimit {
+                price: "9000.55".parse().unwrap(),
+                price2: "9000.5".parse().unwrap(),
+            })
+            .ty(Side::Sell)
+            .pair(pair.clone())
+            .volume("0.000000001".parse::<KrakenFloat>().unwrap())
+            .leverage(3)
+            .close(
+                Close::try_from(OrderType::Limit {
+                    price: "9100.01".parse().unwrap(),
+                })
+                .unwrap(),
+            )
+            .build();
+        assert_eq!(
+            invalid.violations(&info),
+            vec![
+                Violation::VolumeBelowMinimum {
+                    volume: "0.000000001".parse().unwrap(),
+                    ordermin: "0.0001".parse().unwrap(),
+                },
+                Violation::VolumeDecimals {
+                    volume: "0.000000001".parse().unwrap(),
+                    decimals: 8,
+                },
+                Violation::PriceDecimals {
+                    price: "9000.55".parse().unwrap(),
+                    decimals: 1,
+                },
+                Violation::PriceDecimals {
+                    price: "9100.01".parse().unwrap(),
+                    decimals: 1,
+                },
+                Violation::Leverage {
+                    leverage: 3.into(),
+                    allowed: vec![2].into_iter().collect(),
+                },
+            ]
+        );
+
+        // the volume of a viqc order is in the quote currency
+        let viqc = AddOrder::builder()
+            .ordertype(OrderType::Market)
+            .ty(Side::Buy)
+            .pair(pair)
+            .volume("0.000000001".parse::<KrakenFloat>().unwrap())
+            .oflags(OrderFlags::new(vec![OrderFlag::Viqc]).unwrap())
+            .build();
+        assert!(viqc.violations(&info).is_empty());
+
+        let eth_usd = AddOrder::builder()
+            .ordertype(OrderType::Market)
+            .ty(Side::Buy)
+            .pair("ETH/USD".parse::<CurrencyPair>().unwrap())
+            .volume(1)
+            .build();
+        assert_eq!(
+            eth_usd.violations(&info),
+            vec![Violation::WrongPair {
+                pair: "ETH/USD".parse().unwrap(),
+                altname: "XBTUSD".to_string(),
+            }]
+        );
     }
 
     #[test]
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 
 pub mod subscription_status {
     use crate::core::CurrencyPair;
+    use crate::message::channel::{ChannelName, Depth, Echoed, Interval};
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
@@ -10,15 +11,16 @@ pub mod subscription_status {
         SubscriptionStatus,
     }
 
+    // An error status echoes back invalid values as sent, e.g. an unsupported depth.
     #[derive(Debug, Deserialize, Serialize, Clone)]
     pub struct Subscription {
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub depth: Option<i64>,
+        pub depth: Option<Echoed<Depth, i64>>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub interval: Option<i64>,
+        pub interval: Option<Echoed<Interval, i64>>,
 
-        pub name: String,
+        pub name: Echoed<ChannelName, String>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         pub token: Option<String>,
@@ -206,6 +208,20 @@ pub enum Response {
     CancelOrderStatus(CancelOrderStatus),
 }
 
+impl Response {
+    // The `reqid` of the request this is a response to.
+    pub fn reqid(&self) -> Option<i64> {
+        match self {
+            Response::Error(r) => r.reqid,
+            Response::SubscriptionStatus(r) => r.reqid,
+            Response::Pong(r) => r.reqid,
+            Response::AddOrderStatus(r) => r.reqid,
+            Response::CancelAllStatus(r) => r.reqid,
+            Response::CancelOrderStatus(r) => r.reqid,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -250,7 +266,9 @@ mod test {
 
     #[test]
     fn test_subscription_status() {
-        let payloads = vec![
+        use crate::message::channel::{ChannelName, Depth, Echoed, Interval};
+
+        let payloads = [
             r#"{
             "channelID": 10001,
             "channelName": "ticker",
@@ -292,11 +310,23 @@ mod test {
             }
           }"#,
         ];
-        for payload in payloads {
+        for payload in payloads.iter() {
             let value: SubscriptionStatus = serde_json::from_str(payload).unwrap();
             let json = serde_json::to_string(&value).unwrap();
             assert_eq!(json::parse(payload).unwrap(), json::parse(&json).unwrap());
         }
+
+        let value: SubscriptionStatus = serde_json::from_str(payloads[1]).unwrap();
+        assert_eq!(value.subscription.name, Echoed::Valid(ChannelName::Ohlc));
+        assert_eq!(
+            value.subscription.interval,
+            Some(Echoed::Valid(Interval::FiveMinutes))
+        );
+        let value: SubscriptionStatus = serde_json::from_str(payloads[3]).unwrap();
+        assert_eq!(
+            value.subscription.depth,
+            Some(Echoed::<Depth, _>::Invalid(42))
+        );
     }
 
     #[test]
@@ -0,0 +1,405 @@
+use crate::core::{CurrencyPair, KrakenFloat};
+use crate::message::publication::{
+    book::{snapshot, update},
+    BookSnapshot, BookUpdate, Publication,
+};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub price: KrakenFloat,
+    pub volume: KrakenFloat,
+    pub timestamp: KrakenFloat,
+}
+
+impl From<&snapshot::PriceLevelData> for Level {
+    fn from(data: &snapshot::PriceLevelData) -> Self {
+        Self {
+            price: data.price,
+            volume: data.volume,
+            timestamp: data.timestamp,
+        }
+    }
+}
+
+impl From<&update::PriceLevelData> for Level {
+    fn from(data: &update::PriceLevelData) -> Self {
+        Self {
+            price: data.price,
+            volume: data.volume,
+            timestamp: data.timestamp,
+        }
+    }
+}
+
+// Parses the depth out of a book channel name, e.g. `book-25` -> 25.
+fn channel_depth(channel_name: &str) -> Option<usize> {
+    let mut split = channel_name.split('-');
+    match (split.next(), split.next()) {
+        (Some("book"), Some(depth)) => depth.parse().ok(),
+        _ => None,
+    }
+}
+
+// Number of levels per side covered by the book checksum.
+const CHECKSUM_DEPTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumMismatch {
+    pub pair: CurrencyPair,
+    pub depth: usize,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} book checksum mismatch: expected {}, computed {}",
+            self.pair, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+// Kraken's checksum is computed over the digits exactly as they are sent, which `KrakenFloat`
+// preserves along with their scale.
+fn checksum_digits(value: KrakenFloat) -> String {
+    String::from(value)
+        .replace('.', "")
+        .trim_start_matches('0')
+        .to_string()
+}
+
+// A level-2 order book for a single pair, kept in sync from `book` publications.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pair: CurrencyPair,
+    depth: usize,
+    asks: BTreeMap<KrakenFloat, Level>,
+    bids: BTreeMap<KrakenFloat, Level>,
+}
+
+impl OrderBook {
+    pub fn new(pair: CurrencyPair, depth: usize) -> Self {
+        Self {
+            pair,
+            depth,
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> Result<Self, crate::Error> {
+        let depth = channel_depth(&snapshot.channel_name).ok_or_else(|| {
+            crate::Error::parse(snapshot.channel_name.as_str(), "invalid book channel name")
+        })?;
+        let mut book = Self::new(snapshot.pair.clone(), depth);
+        book.apply_snapshot(snapshot);
+        Ok(book)
+    }
+
+    pub fn pair(&self) -> &CurrencyPair {
+        &self.pair
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Kraken's CRC32 over the top 10 asks then the top 10 bids.
+    pub fn checksum(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        for level in self.asks(CHECKSUM_DEPTH).chain(self.bids(CHECKSUM_DEPTH)) {
+            hasher.update(checksum_digits(level.price).as_bytes());
+            hasher.update(checksum_digits(level.volume).as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) {
+        self.asks = snapshot
+            .data
+            .ask
+            .iter()
+            .map(|data| (data.price, Level::from(data)))
+            .collect();
+        self.bids = snapshot
+            .data
+            .bid
+            .iter()
+            .map(|data| (data.price, Level::from(data)))
+            .collect();
+        self.truncate();
+    }
+
+    // Republished levels (`update_type == "r"`) are levels falling back into the subscribed depth
+    // and are applied exactly like any other update.
+    // The update's checksum is verified afterwards.
+    pub fn apply_update(&mut self, update: &BookUpdate) -> Result<(), ChecksumMismatch> {
+        if let Some(ask) = &update.ask {
+            for data in ask.updates.iter() {
+                Self::apply_level(&mut self.asks, data);
+            }
+        }
+        if let Some(bid) = &update.bid {
+            for data in bid.updates.iter() {
+                Self::apply_level(&mut self.bids, data);
+            }
+        }
+        self.truncate();
+
+        // when both sides are updated the checksum is sent with the bids
+        let expected = update
+            .bid
+            .as_ref()
+            .and_then(|bid| bid.checksum.as_ref())
+            .or_else(|| update.ask.as_ref().and_then(|ask| ask.checksum.as_ref()))
+            .and_then(|checksum| checksum.parse::<u32>().ok());
+
+        match (expected, self.checksum()) {
+            (Some(expected), actual) if expected != actual => Err(ChecksumMismatch {
+                pair: self.pair.clone(),
+                depth: self.depth,
+                expected,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply_level(side: &mut BTreeMap<KrakenFloat, Level>, data: &update::PriceLevelData) {
+        if data.volume.is_zero() {
+            side.remove(&data.price);
+        } else {
+            side.insert(data.price, Level::from(data));
+        }
+    }
+
+    fn truncate(&mut self) {
+        while self.asks.len() > self.depth {
+            let worst = *self.asks.keys().next_back().unwrap();
+            self.asks.remove(&worst);
+        }
+        while self.bids.len() > self.depth {
+            let worst = *self.bids.keys().next().unwrap();
+            self.bids.remove(&worst);
+        }
+    }
+
+    pub fn best_ask(&self) -> Option<&Level> {
+        self.asks.values().next()
+    }
+
+    pub fn best_bid(&self) -> Option<&Level> {
+        self.bids.values().next_back()
+    }
+
+    pub fn mid(&self) -> Option<KrakenFloat> {
+        let ask = self.best_ask()?;
+        let bid = self.best_bid()?;
+        ask.price
+            .checked_add(bid.price)?
+            .checked_div(KrakenFloat::from(2))
+    }
+
+    pub fn spread(&self) -> Option<KrakenFloat> {
+        let ask = self.best_ask()?;
+        let bid = self.best_bid()?;
+        ask.price.checked_sub(bid.price)
+    }
+
+    // The best `n` ask levels, lowest price first.
+    pub fn asks(&self, n: usize) -> impl Iterator<Item = &Level> {
+        self.asks.values().take(n)
+    }
+
+    // The best `n` bid levels, highest price first.
+    pub fn bids(&self, n: usize) -> impl Iterator<Item = &Level> {
+        self.bids.values().rev().take(n)
+    }
+}
+
+// One `OrderBook` per subscribed pair.
+#[derive(Debug, Clone, Default)]
+pub struct OrderBooks {
+    books: HashMap<CurrencyPair, OrderBook>,
+}
+
+impl OrderBooks {
+    pub fn get(&self, pair: &CurrencyPair) -> Option<&OrderBook> {
+        self.books.get(pair)
+    }
+
+    pub fn remove(&mut self, pair: &CurrencyPair) -> Option<OrderBook> {
+        self.books.remove(pair)
+    }
+
+    // Applies a `book` publication, returning the book it touched.
+    // Any other publication, or an update for a pair without a snapshot yet, is ignored.
+    // A book whose checksum doesn't match is dropped until the next snapshot re-seeds it.
+    pub fn apply(
+        &mut self,
+        publication: &Publication,
+    ) -> Result<Option<&OrderBook>, ChecksumMismatch> {
+        match publication {
+            Publication::BookSnapshot(snapshot) => {
+                let book = match OrderBook::from_snapshot(snapshot) {
+                    Ok(book) => book,
+                    Err(_) => return Ok(None),
+                };
+                self.books.insert(snapshot.pair.clone(), book);
+                Ok(self.books.get(&snapshot.pair))
+            }
+            Publication::BookUpdate(update) => {
+                let book = match self.books.get_mut(&update.pair) {
+                    Some(book) => book,
+                    None => return Ok(None),
+                };
+                if let Err(mismatch) = book.apply_update(update) {
+                    self.books.remove(&update.pair);
+                    return Err(mismatch);
+                }
+                Ok(self.books.get(&update.pair))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> BookSnapshot {
+        serde_json::from_str(
+            r#"
+        [
+            0,
+            {
+                "as": [
+                    ["5541.30000", "2.50700000", "1534614248.123678"],
+                    ["5541.80000", "0.33000000", "1534614098.345543"],
+                    ["5542.70000", "0.64700000", "1534614244.654432"]
+                ],
+                "bs": [
+                    ["5541.20000", "1.52900000", "1534614248.765567"],
+                    ["5539.90000", "0.30000000", "1534614241.769870"],
+                    ["5539.50000", "5.00000000", "1534613831.243486"]
+                ]
+            },
+            "book-3",
+            "XBT/USD"
+        ]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn prices<'a>(levels: impl Iterator<Item = &'a Level>) -> Vec<String> {
+        levels.map(|level| level.price.to_string()).collect()
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let book = OrderBook::from_snapshot(&snapshot()).unwrap();
+        assert_eq!(book.depth(), 3);
+        assert_eq!(book.best_ask().unwrap().price, KrakenFloat::new(55413, 1));
+        assert_eq!(book.best_bid().unwrap().price, KrakenFloat::new(55412, 1));
+        assert_eq!(book.spread(), Some(KrakenFloat::new(1, 1)));
+        assert_eq!(book.mid(), Some(KrakenFloat::new(554125, 2)));
+        assert_eq!(prices(book.asks(2)), vec!["5541.30000", "5541.80000"]);
+        assert_eq!(
+            prices(book.bids(10)),
+            vec!["5541.20000", "5539.90000", "5539.50000"]
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let mut books = OrderBooks::default();
+        books.apply(&Publication::BookSnapshot(snapshot())).unwrap();
+
+        let update: BookUpdate = serde_json::from_str(
+            r#"[
+                0,
+                {"a": [["5541.30000", "0.00000000", "1534614335.345903"]]},
+                {"b": [["5541.25000", "1.00000000", "1534614335.345903"]]},
+                "book-3",
+                "XBT/USD"
+            ]"#,
+        )
+        .unwrap();
+        let book = books
+            .apply(&Publication::BookUpdate(update))
+            .unwrap()
+            .unwrap();
+
+        // deleted level
+        assert_eq!(prices(book.asks(10)), vec!["5541.80000", "5542.70000"]);
+        // new best bid pushes the worst bid out of the subscribed depth
+        assert_eq!(
+            prices(book.bids(10)),
+            vec!["5541.25000", "5541.20000", "5539.90000"]
+        );
+
+        let republish: BookUpdate = serde_json::from_str(
+            r#"[
+                0,
+                {"a": [["5543.00000", "0.20000000", "1534614335.345903", "r"]]},
+                "book-3",
+                "XBT/USD"
+            ]"#,
+        )
+        .unwrap();
+        let book = books
+            .apply(&Publication::BookUpdate(republish))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            prices(book.asks(10)),
+            vec!["5541.80000", "5542.70000", "5543.00000"]
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let pair = "XBT/USD".parse::<CurrencyPair>().unwrap();
+
+        let book = OrderBook::from_snapshot(&snapshot()).unwrap();
+        assert_eq!(book.checksum(), 1710400350);
+
+        let mut books = OrderBooks::default();
+        books.apply(&Publication::BookSnapshot(snapshot())).unwrap();
+
+        let update: BookUpdate = serde_json::from_str(
+            r#"[
+                0,
+                {"a": [["5541.30000", "0.00000000", "1534614335.345903"]]},
+                {"b": [["5541.25000", "1.00000000", "1534614335.345903"]], "c": "3318863536"},
+                "book-3",
+                "XBT/USD"
+            ]"#,
+        )
+        .unwrap();
+        assert!(books.apply(&Publication::BookUpdate(update)).is_ok());
+
+        let corrupted: BookUpdate = serde_json::from_str(
+            r#"[
+                0,
+                {"a": [["5542.70000", "0.00000000", "1534614335.345903"]], "c": "3318863536"},
+                "book-3",
+                "XBT/USD"
+            ]"#,
+        )
+        .unwrap();
+        let mismatch = books
+            .apply(&Publication::BookUpdate(corrupted))
+            .unwrap_err();
+        assert_eq!(mismatch.pair, pair);
+        assert_eq!(mismatch.expected, 3318863536);
+        assert!(books.get(&pair).is_none());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::core::CurrencyPair;
+use crate::Error;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Orders older than this are cancelled without a penalty.
+const MAX_PENALTY_AGE: Duration = Duration::from_secs(300);
+
+// The verification tier of the account, which sets how fast Kraken's counters decay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tier {
+    Starter,
+    Intermediate,
+    Pro,
+}
+
+impl Tier {
+    // The maximum and the decay per second of the REST API call counter.
+    fn api_limit(&self) -> (f64, f64) {
+        match self {
+            Tier::Starter => (15.0, 0.33),
+            Tier::Intermediate => (20.0, 0.5),
+            Tier::Pro => (20.0, 1.0),
+        }
+    }
+
+    // The maximum and the decay per second of the per pair trading counter.
+    fn order_limit(&self) -> (f64, f64) {
+        match self {
+            Tier::Starter => (60.0, 1.0),
+            Tier::Intermediate => (125.0, 2.34),
+            Tier::Pro => (180.0, 3.75),
+        }
+    }
+}
+
+// What to do with a call that would exceed a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Wait until the counter has decayed enough.
+    Wait,
+    // Return `Error::RateLimited` right away, without counting the call.
+    FailFast,
+}
+
+// An order request, counted against the trading counter of its pair.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderAction {
+    Add(CurrencyPair),
+    Cancel(Vec<String>),
+}
+
+// The cost of a private REST `method`: ledger and trade history queries count twice, order
+// placement and cancellation only count towards the trading counter.
+pub fn default_cost(method: &str) -> u32 {
+    match method {
+        "Ledgers" | "QueryLedgers" | "TradesHistory" | "QueryTrades" => 2,
+        "AddOrder" | "CancelOrder" | "CancelAll" | "CancelAllOrdersAfter" => 0,
+        _ => 1,
+    }
+}
+
+// Kraken's penalty for cancelling an order, the younger the order the higher.
+fn cancel_cost(age: Duration) -> u32 {
+    match age.as_secs() {
+        0..=4 => 8,
+        5..=9 => 6,
+        10..=14 => 5,
+        15..=44 => 4,
+        45..=89 => 2,
+        90..=299 => 1,
+        _ => 0,
+    }
+}
+
+// A counter increased by every call and decaying linearly over time.
+// Calls are counted when they're let through, even if they still have to wait, so the value can
+// exceed `max` by the calls queued behind it.
+#[derive(Debug, Clone)]
+struct Counter {
+    value: f64,
+    max: f64,
+    decay: f64,
+    updated: Instant,
+}
+
+impl Counter {
+    fn new((max, decay): (f64, f64), now: Instant) -> Self {
+        Self {
+            value: 0.0,
+            max,
+            decay,
+            updated: now,
+        }
+    }
+
+    fn decay(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.value = (self.value - elapsed * self.decay).max(0.0);
+        self.updated = self.updated.max(now);
+    }
+
+    // How long until `cost` fits under the maximum.
+    fn wait(&mut self, cost: u32, now: Instant) -> Duration {
+        self.decay(now);
+        let excess = self.value + f64::from(cost) - self.max;
+        if excess <= 0.0 {
+            Duration::default()
+        } else {
+            Duration::from_secs_f64(excess / self.decay)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    api: Counter,
+    orders: HashMap<CurrencyPair, Counter>,
+    // when each order was placed, to price its cancellation
+    placed: HashMap<String, (CurrencyPair, Instant)>,
+}
+
+// A client side model of Kraken's rate limits: the per API key call counter of the private REST
+// endpoints and the per pair trading counter of order placement and cancellation.
+// Clones share their counters, so a `rest_api::Client` and a `ws::Client` trading on the same key
+// should be given clones of the same limiter.
+//
+// The model only sees the calls made through it and is at best an approximation of Kraken's
+// counters, e.g. cancelling an order placed elsewhere is assumed to carry no penalty.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    tier: Tier,
+    mode: Mode,
+    costs: HashMap<String, u32>,
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    pub fn new(tier: Tier) -> Self {
+        let now = Instant::now();
+        Self {
+            tier,
+            mode: Mode::Wait,
+            costs: HashMap::new(),
+            state: Arc::new(Mutex::new(State {
+                api: Counter::new(tier.api_limit(), now),
+                orders: HashMap::new(),
+                placed: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // Overrides the `default_cost` of a private REST `method`.
+    pub fn with_cost<S: Into<String>>(mut self, method: S, cost: u32) -> Self {
+        self.costs.insert(method.into(), cost);
+        self
+    }
+
+    pub fn tier(&self) -> Tier {
+        self.tier
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn cost(&self, method: &str) -> u32 {
+        self.costs
+            .get(method)
+            .copied()
+            .unwrap_or_else(|| default_cost(method))
+    }
+
+    // Counts a call to the private REST `method`, waiting for the counter to allow it.
+    pub async fn call(&self, method: &str) -> Result<(), Error> {
+        let wait = self.reserve_call(method, Instant::now())?;
+        tokio::time::delay_for(wait).await;
+        Ok(())
+    }
+
+    // Counts an order placement or cancellation, waiting for the counters of its pairs to allow
+    // it.
+    pub async fn order(&self, order: &OrderAction) -> Result<(), Error> {
+        let wait = self.reserve_order(order, Instant::now())?;
+        tokio::time::delay_for(wait).await;
+        Ok(())
+    }
+
+    // Remembers when the order `txid` was placed, which sets the cost of cancelling it.
+    pub fn placed(&self, pair: &CurrencyPair, txid: &str) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state
+            .placed
+            .retain(|_, (_, placed)| now.saturating_duration_since(*placed) < MAX_PENALTY_AGE);
+        state.placed.insert(txid.to_string(), (pair.clone(), now));
+    }
+
+    // Forgets the order `txid` once it's cancelled, it can't be cancelled again.
+    pub fn cancelled(&self, txid: &str) {
+        self.state.lock().unwrap().placed.remove(txid);
+    }
+
+    // Counts a call to `method`, returning how long it has to wait before being sent.
+    pub(crate) fn reserve_call(&self, method: &str, now: Instant) -> Result<Duration, Error> {
+        let cost = self.cost(method);
+        let mut state = self.state.lock().unwrap();
+        let wait = state.api.wait(cost, now);
+        self.check(wait)?;
+        state.api.value += f64::from(cost);
+        Ok(wait)
+    }
+
+    // Counts an order request, returning how long it has to wait before being sent.
+    // A cancellation of several orders waits for the slowest of their pairs.
+    pub(crate) fn reserve_order(
+        &self,
+        order: &OrderAction,
+        now: Instant,
+    ) -> Result<Duration, Error> {
+        let mut state = self.state.lock().unwrap();
+        let costs = match order {
+            OrderAction::Add(pair) => vec![(pair.clone(), 1)],
+            OrderAction::Cancel(txids) => txids
+                .iter()
+                .filter_map(|txid| state.placed.get(txid))
+                .map(|(pair, placed)| {
+                    let cost = cancel_cost(now.saturating_duration_since(*placed));
+                    (pair.clone(), cost)
+                })
+                .collect(),
+        };
+
+        let limit = self.tier.order_limit();
+        let mut wait = Duration::default();
+        for (pair, cost) in costs.iter() {
+            let counter = state
+                .orders
+                .entry(pair.clone())
+                .or_insert_with(|| Counter::new(limit, now));
+            wait = wait.max(counter.wait(*cost, now));
+        }
+        self.check(wait)?;
+
+        for (pair, cost) in costs {
+            if let Some(counter) = state.orders.get_mut(&pair) {
+                counter.value += f64::from(cost);
+            }
+        }
+        Ok(wait)
+    }
+
+    fn check(&self, wait: Duration) -> Result<(), Error> {
+        if self.mode == Mode::FailFast && wait > Duration::default() {
+            return Err(Error::RateLimited(wait));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn test_api_counter() {
+        let limiter = RateLimiter::new(Tier::Starter);
+        let now = Instant::now();
+        for _ in 0..7 {
+            assert_eq!(
+                limiter.reserve_call("Ledgers", now).unwrap(),
+                Duration::default()
+            );
+        }
+        assert_eq!(
+            limiter.reserve_call("Balance", now).unwrap(),
+            Duration::default()
+        );
+
+        // the counter is full, the next call waits for it to decay by one
+        let wait = limiter.reserve_call("Balance", now).unwrap();
+        assert!((wait.as_secs_f64() - 1.0 / 0.33).abs() < 1e-6);
+
+        // and once it has, queued calls still count
+        let later = now + secs(1.0);
+        assert!(limiter.reserve_call("Balance", later).unwrap() > Duration::default());
+
+        // order calls don't count
+        assert_eq!(limiter.cost("AddOrder"), 0);
+        let limiter = limiter.with_cost("Balance", 3);
+        assert_eq!(limiter.cost("Balance"), 3);
+    }
+
+    #[test]
+    fn test_fail_fast() {
+        let limiter = RateLimiter::new(Tier::Pro).with_mode(Mode::FailFast);
+        let now = Instant::now();
+        for _ in 0..10 {
+            limiter.reserve_call("QueryTrades", now).unwrap();
+        }
+        match limiter.reserve_call("Balance", now) {
+            Err(Error::RateLimited(wait)) => assert_eq!(wait, secs(1.0)),
+            result => panic!("expected Error::RateLimited, got {:?}", result),
+        }
+
+        // rejected calls aren't counted
+        let later = now + secs(1.0);
+        assert_eq!(
+            limiter.reserve_call("Balance", later).unwrap(),
+            Duration::default()
+        );
+        assert!(limiter.reserve_call("Balance", later).is_err());
+    }
+
+    #[test]
+    fn test_order_counter() {
+        let limiter = RateLimiter::new(Tier::Starter).with_mode(Mode::FailFast);
+        let xbt_usd: CurrencyPair = "XBT/USD".parse().unwrap();
+        let eth_usd: CurrencyPair = "ETH/USD".parse().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            limiter
+                .reserve_order(&OrderAction::Add(xbt_usd.clone()), now)
+                .unwrap();
+        }
+        assert!(limiter
+            .reserve_order(&OrderAction::Add(xbt_usd.clone()), now)
+            .is_err());
+        // each pair has its own counter
+        limiter
+            .reserve_order(&OrderAction::Add(eth_usd.clone()), now)
+            .unwrap();
+
+        // cancelling a young order costs more than an old one
+        limiter.placed(&eth_usd, "OQCLML-BW3P3-BUCMWZ");
+        let cancel = OrderAction::Cancel(vec!["OQCLML-BW3P3-BUCMWZ".to_string()]);
+        let placed = Instant::now();
+        limiter.reserve_order(&cancel, placed).unwrap();
+        {
+            let state = limiter.state.lock().unwrap();
+            assert!((state.orders[&eth_usd].value - 9.0).abs() < 1e-2);
+        }
+
+        // cancelled orders are forgotten
+        limiter.cancelled("OQCLML-BW3P3-BUCMWZ");
+        assert!(limiter.state.lock().unwrap().placed.is_empty());
+
+        // unknown orders are free to cancel
+        let cancel = OrderAction::Cancel(vec!["unknown".to_string()]);
+        assert_eq!(
+            limiter.reserve_order(&cancel, now).unwrap(),
+            Duration::default()
+        );
+
+        assert_eq!(cancel_cost(secs(4.9)), 8);
+        assert_eq!(cancel_cost(secs(60.0)), 2);
+        assert_eq!(cancel_cost(MAX_PENALTY_AGE), 0);
+    }
+}
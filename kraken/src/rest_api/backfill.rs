@@ -0,0 +1,165 @@
+use super::request::TradeHistory;
+use super::response::trade_history::{self, TradeData};
+use crate::core::CurrencyPair;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+// Kraken allows roughly one public call per second before answering with
+// "EAPI:Rate limit exceeded".
+const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+
+// The pagination state of a trade history download between `since` inclusive and `until`
+// exclusive, see `Client::backfill`.
+//
+// Each page starts at the `last` cursor of the previous one, which is the time of its newest
+// trade, so trades sharing that time are sent twice and are dropped from the second page.
+#[derive(Debug, Clone)]
+pub struct Backfill {
+    pair: CurrencyPair,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    cursor: DateTime<Utc>,
+    // the trades at the newest time of the previous page
+    boundary: Vec<TradeData>,
+    done: bool,
+    delay: Duration,
+}
+
+impl Backfill {
+    pub fn new(pair: CurrencyPair, since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        Self {
+            pair,
+            since,
+            until,
+            cursor: since,
+            boundary: Vec::new(),
+            done: since >= until,
+            delay: DEFAULT_DELAY,
+        }
+    }
+
+    // The time to wait between two requests, doubled on every retryable error, e.g. a rate limit.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // The request for the next page, if any.
+    pub fn request(&self) -> Option<TradeHistory> {
+        if self.done {
+            return None;
+        }
+        Some(
+            TradeHistory::builder()
+                .pair(self.pair.clone())
+                .since(self.cursor)
+                .build(),
+        )
+    }
+
+    // Takes the response to `request`, returning its trades within the range that weren't part
+    // of the previous page, oldest first.
+    pub fn page(&mut self, result: trade_history::Result) -> Vec<TradeData> {
+        let trades = result.data.trade_data;
+        let newest = trades.iter().map(|trade| trade.time).max();
+        let mut previous = std::mem::replace(
+            &mut self.boundary,
+            trades
+                .iter()
+                .filter(|trade| Some(trade.time) == newest)
+                .cloned()
+                .collect(),
+        );
+
+        let mut page = Vec::with_capacity(trades.len());
+        for trade in trades {
+            if let Some(i) = previous.iter().position(|seen| *seen == trade) {
+                previous.swap_remove(i);
+            } else if trade.time.0 >= self.since && trade.time.0 < self.until {
+                page.push(trade);
+            }
+        }
+
+        // an empty page or a cursor that didn't move means there's nothing newer yet
+        self.done = newest.is_none() || result.last <= self.cursor || result.last >= self.until;
+        self.cursor = result.last;
+        page
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn page(last: i64, trades: &[(&str, f64)]) -> trade_history::Result {
+        let trades = trades
+            .iter()
+            .map(|(price, time)| format!(r#"["{}","1.0",{},"b","l",""]"#, price, time))
+            .collect::<Vec<_>>()
+            .join(",");
+        serde_json::from_str(&format!(
+            r#"{{"XXBTZUSD":[{}],"last":"{}"}}"#,
+            trades,
+            last * 1_000_000_000
+        ))
+        .unwrap()
+    }
+
+    fn prices(trades: &[TradeData]) -> Vec<String> {
+        trades.iter().map(|trade| trade.price.to_string()).collect()
+    }
+
+    fn time(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_backfill() {
+        let pair = "XBT/USD".parse().unwrap();
+        let mut backfill = Backfill::new(pair, time(10), time(40));
+        assert_eq!(
+            backfill.request().unwrap().query().unwrap(),
+            "pair=XBTUSD&since=10000000000"
+        );
+
+        let trades = backfill.page(page(20, &[("1.0", 10.0), ("2.0", 15.0), ("3.0", 20.0)]));
+        assert_eq!(prices(&trades), vec!["1.0", "2.0", "3.0"]);
+        assert!(!backfill.is_done());
+        assert_eq!(
+            backfill.request().unwrap().query().unwrap(),
+            "pair=XBTUSD&since=20000000000"
+        );
+
+        // the trade at the cursor is sent again
+        let trades = backfill.page(page(30, &[("3.0", 20.0), ("4.0", 20.0), ("5.0", 30.0)]));
+        assert_eq!(prices(&trades), vec!["4.0", "5.0"]);
+
+        // trades past `until` are dropped and end the backfill
+        let trades = backfill.page(page(45, &[("5.0", 30.0), ("6.0", 39.5), ("7.0", 45.0)]));
+        assert_eq!(prices(&trades), vec!["6.0"]);
+        assert!(backfill.is_done());
+        assert!(backfill.request().is_none());
+    }
+
+    #[test]
+    fn test_backfill_caught_up() {
+        let pair: CurrencyPair = "XBT/USD".parse().unwrap();
+        let mut backfill = Backfill::new(pair.clone(), time(10), time(40));
+        backfill.page(page(20, &[("1.0", 20.0)]));
+        assert!(!backfill.is_done());
+
+        // nothing newer than the cursor
+        assert!(backfill.page(page(20, &[("1.0", 20.0)])).is_empty());
+        assert!(backfill.is_done());
+
+        assert!(Backfill::new(pair, time(40), time(10)).request().is_none());
+    }
+}
@@ -0,0 +1,490 @@
+use super::backfill::Backfill;
+use super::request::{self, TradeHistory};
+use super::response::{
+    add_order, asset_pairs, assets, balance, cancel_all, cancel_all_orders_after, cancel_order,
+    closed_orders, depth, ledgers, ohlc, open_orders, open_positions, query_ledgers, query_orders,
+    query_trades, server_time, spread, system_status, ticker, trade_balance,
+    trade_history::{self, TradeData},
+    trade_volume, trades_history, websockets_token, Response,
+};
+use crate::message::{
+    request::{AddOrder, CancelOrder},
+    ParseError,
+};
+use crate::rate_limit::{OrderAction, RateLimiter};
+use crate::Error;
+use futures_util::stream::{self, Stream};
+use hmac::{Hmac, Mac, NewMac};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::time::Instant;
+
+const KRAKEN_API_URL: &str = "https://api.kraken.com";
+
+const RETRIES: u32 = 5;
+
+pub struct Credentials {
+    key: String,
+    secret: Vec<u8>,
+}
+
+impl Credentials {
+    // `secret` is the base64 encoded private key as shown on Kraken's API management page.
+    pub fn new<K: Into<String>, S: AsRef<str>>(key: K, secret: S) -> Result<Self, Error> {
+        Ok(Self {
+            key: key.into(),
+            // the secret itself is left out of the error, it may end up in logs
+            secret: base64::decode(secret.as_ref())
+                .map_err(|_| Error::parse("<secret>", "API secret is not valid base64"))?,
+        })
+    }
+
+    // API-Sign = base64(HMAC-SHA512(path + SHA256(nonce + post data), secret))
+    pub fn sign(&self, path: &str, nonce: u64, post_data: &str) -> String {
+        let mut sha256 = Sha256::new();
+        sha256.update(nonce.to_string().as_bytes());
+        sha256.update(post_data.as_bytes());
+
+        let mut hmac = Hmac::<Sha512>::new_varkey(&self.secret).expect("HMAC accepts any key");
+        hmac.update(path.as_bytes());
+        hmac.update(&sha256.finalize());
+
+        base64::encode(hmac.finalize().into_bytes())
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+// Nonces must strictly increase for a given API key, even across concurrent requests.
+#[derive(Debug, Default)]
+struct Nonce(AtomicU64);
+
+impl Nonce {
+    fn next(&self) -> u64 {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let previous = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                Some(std::cmp::max(last + 1, now))
+            })
+            .unwrap();
+        std::cmp::max(previous + 1, now)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    credentials: Option<Arc<Credentials>>,
+    nonce: Arc<Nonce>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    // A client limited to the public endpoints.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: KRAKEN_API_URL.to_string(),
+            credentials: None,
+            nonce: Arc::new(Nonce::default()),
+            rate_limiter: None,
+        }
+    }
+
+    pub fn with_credentials(credentials: Credentials) -> Self {
+        Self {
+            credentials: Some(Arc::new(credentials)),
+            ..Self::new()
+        }
+    }
+
+    pub fn with_url<S: Into<String>>(self, url: S) -> Self {
+        Self {
+            url: url.into(),
+            ..self
+        }
+    }
+
+    // Consulted before every private call, and before orders and cancellations.
+    pub fn with_rate_limiter(self, rate_limiter: RateLimiter) -> Self {
+        Self {
+            rate_limiter: Some(rate_limiter),
+            ..self
+        }
+    }
+
+    pub async fn public<R: DeserializeOwned>(&self, url: &str) -> Result<R, Error> {
+        let body = self.http.get(url).send().await?.text().await?;
+        Self::parse(&body)
+    }
+
+    // Signs and POSTs `params` to the private `method`, e.g. `GetWebSocketsToken`.
+    pub async fn private<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &[(&str, String)],
+    ) -> Result<R, Error> {
+        let credentials = self.credentials.as_ref().ok_or(Error::MissingCredentials)?;
+        // before taking a nonce, a call waiting with an older one would be rejected
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.call(method).await?;
+        }
+
+        let path = format!("/0/private/{}", method);
+        let nonce = self.nonce.next();
+        let nonce_param = nonce.to_string();
+        let post_data = serde_urlencoded::to_string(
+            std::iter::once(("nonce", &nonce_param))
+                .chain(params.iter().map(|(k, v)| (*k, v)))
+                .collect::<Vec<_>>(),
+        )
+        .expect("form parameters are always serializable");
+
+        let body = self
+            .http
+            .post(&format!("{}{}", self.url, path))
+            .header("API-Key", credentials.key.as_str())
+            .header("API-Sign", credentials.sign(&path, nonce, &post_data))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )
+            .body(post_data)
+            .send()
+            .await?
+            .text()
+            .await?;
+        Self::parse(&body)
+    }
+
+    // GETs the public `method` with the query string `query`, e.g. `Ticker`.
+    async fn public_method<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        query: &str,
+    ) -> Result<R, Error> {
+        let mut url = format!("{}/0/public/{}", self.url, method);
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(query);
+        }
+        self.public(&url).await
+    }
+
+    pub async fn server_time(&self) -> Result<server_time::Result, Error> {
+        self.public_method("Time", "").await
+    }
+
+    pub async fn system_status(&self) -> Result<system_status::Result, Error> {
+        self.public_method("SystemStatus", "").await
+    }
+
+    pub async fn assets(&self, request: &request::Assets) -> Result<assets::Result, Error> {
+        self.public_method("Assets", &request.query()).await
+    }
+
+    pub async fn asset_pairs(&self) -> Result<asset_pairs::Result, Error> {
+        self.public_method("AssetPairs", "").await
+    }
+
+    pub async fn ticker(&self, request: &request::Ticker) -> Result<ticker::Result, Error> {
+        self.public_method("Ticker", &request.query()).await
+    }
+
+    pub async fn ohlc(&self, request: &request::Ohlc) -> Result<ohlc::Result, Error> {
+        self.public_method("OHLC", &request.query()).await
+    }
+
+    pub async fn depth(&self, request: &request::Depth) -> Result<depth::Result, Error> {
+        self.public_method("Depth", &request.query()).await
+    }
+
+    pub async fn spread(&self, request: &request::Spread) -> Result<spread::Result, Error> {
+        self.public_method("Spread", &request.query()).await
+    }
+
+    pub async fn trade_history(
+        &self,
+        request: &TradeHistory,
+    ) -> Result<trade_history::Result, Error> {
+        self.public_method("Trades", &request.query()?).await
+    }
+
+    // Streams every trade of the backfill's range, oldest first, waiting `Backfill::delay` between
+    // pages and backing off on retryable errors such as rate limits. The stream ends after the first error.
+    pub fn backfill(&self, backfill: Backfill) -> impl Stream<Item = Result<TradeData, Error>> {
+        let state = (self.clone(), backfill, VecDeque::new(), None);
+        stream::unfold(Some(state), |state| async move {
+            let (client, mut backfill, mut trades, mut last_request) = state?;
+            loop {
+                if let Some(trade) = trades.pop_front() {
+                    return Some((Ok(trade), Some((client, backfill, trades, last_request))));
+                }
+
+                let request = backfill.request()?;
+                match client.page(&request, &backfill, &mut last_request).await {
+                    Ok(result) => trades.extend(backfill.page(result)),
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        })
+    }
+
+    async fn page(
+        &self,
+        request: &TradeHistory,
+        backfill: &Backfill,
+        last_request: &mut Option<Instant>,
+    ) -> Result<trade_history::Result, Error> {
+        let mut delay = backfill.delay();
+        let mut retries = 0;
+        loop {
+            if let Some(last_request) = *last_request {
+                tokio::time::delay_until(last_request + delay).await;
+            }
+            *last_request = Some(Instant::now());
+
+            match self.trade_history(request).await {
+                // trade history queries are idempotent, so unknown outcomes are retried too
+                Err(e) if retries < RETRIES && (e.is_retryable() || e.is_outcome_unknown()) => {
+                    retries += 1;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn get_websockets_token(&self) -> Result<websockets_token::Result, Error> {
+        self.private("GetWebSocketsToken", &[]).await
+    }
+
+    pub async fn balance(&self) -> Result<balance::Result, Error> {
+        self.private("Balance", &[]).await
+    }
+
+    pub async fn trade_balance(
+        &self,
+        request: &request::TradeBalance,
+    ) -> Result<trade_balance::Result, Error> {
+        self.private("TradeBalance", &request.params()).await
+    }
+
+    pub async fn open_orders(
+        &self,
+        request: &request::OpenOrders,
+    ) -> Result<open_orders::Result, Error> {
+        self.private("OpenOrders", &request.params()).await
+    }
+
+    pub async fn closed_orders(
+        &self,
+        request: &request::ClosedOrders,
+    ) -> Result<closed_orders::Result, Error> {
+        self.private("ClosedOrders", &request.params()).await
+    }
+
+    pub async fn query_orders(
+        &self,
+        request: &request::QueryOrders,
+    ) -> Result<query_orders::Result, Error> {
+        self.private("QueryOrders", &request.params()).await
+    }
+
+    pub async fn trades_history(
+        &self,
+        request: &request::TradesHistory,
+    ) -> Result<trades_history::Result, Error> {
+        self.private("TradesHistory", &request.params()).await
+    }
+
+    pub async fn query_trades(
+        &self,
+        request: &request::QueryTrades,
+    ) -> Result<query_trades::Result, Error> {
+        self.private("QueryTrades", &request.params()).await
+    }
+
+    pub async fn open_positions(
+        &self,
+        request: &request::OpenPositions,
+    ) -> Result<open_positions::Result, Error> {
+        self.private("OpenPositions", &request.params()).await
+    }
+
+    pub async fn ledgers(&self, request: &request::Ledgers) -> Result<ledgers::Result, Error> {
+        self.private("Ledgers", &request.params()?).await
+    }
+
+    pub async fn query_ledgers(
+        &self,
+        request: &request::QueryLedgers,
+    ) -> Result<query_ledgers::Result, Error> {
+        self.private("QueryLedgers", &request.params()).await
+    }
+
+    pub async fn trade_volume(
+        &self,
+        request: &request::TradeVolume,
+    ) -> Result<trade_volume::Result, Error> {
+        self.private("TradeVolume", &request.params()).await
+    }
+
+    // Places an order over REST, e.g. while the websocket is down.
+    // The order's `token` and `reqid` only apply to the websocket and aren't sent.
+    pub async fn add_order(&self, order: &AddOrder) -> Result<add_order::Result, Error> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .order(&OrderAction::Add(order.pair.clone()))
+                .await?;
+        }
+        let params = request::order_params(order)?;
+        let params = params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect::<Vec<_>>();
+        let result: add_order::Result = self.private("AddOrder", &params).await?;
+        if let Some(rate_limiter) = &self.rate_limiter {
+            for txid in result.txid.iter() {
+                rate_limiter.placed(&order.pair, txid);
+            }
+        }
+        Ok(result)
+    }
+
+    // The REST endpoint cancels a single order per call, so each of `cancel.txid` is cancelled in
+    // turn and its outcome returned along with it, whether or not the others failed.
+    pub async fn cancel_order(
+        &self,
+        cancel: &CancelOrder,
+    ) -> Result<Vec<(String, Result<cancel_order::Result, Error>)>, Error> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .order(&OrderAction::Cancel(cancel.txid.clone()))
+                .await?;
+        }
+        let mut outcomes = Vec::new();
+        for txid in cancel.txid.iter() {
+            let result = self.private("CancelOrder", &[("txid", txid.clone())]).await;
+            if let (Some(rate_limiter), Ok(_)) = (&self.rate_limiter, &result) {
+                rate_limiter.cancelled(txid);
+            }
+            outcomes.push((txid.clone(), result));
+        }
+        Ok(outcomes)
+    }
+
+    pub async fn cancel_all(&self) -> Result<cancel_all::Result, Error> {
+        self.private("CancelAll", &[]).await
+    }
+
+    // Cancels every order unless called again within `timeout`, a zero `timeout` disables the
+    // timer. Kraken counts in whole seconds, so `timeout` is rounded up.
+    pub async fn cancel_all_orders_after(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<cancel_all_orders_after::Result, Error> {
+        let seconds = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+        self.private("CancelAllOrdersAfter", &[("timeout", seconds.to_string())])
+            .await
+    }
+
+    fn parse<R: DeserializeOwned>(body: &str) -> Result<R, Error> {
+        serde_json::from_str::<Response<R>>(body)
+            .map_err(|e| Error::protocol(body, ParseError::Malformed(e)))?
+            .into_result()
+            .map_err(Error::Exchange)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // example from https://docs.kraken.com/rest/#section/Authentication/Headers-and-Signature
+        let credentials = Credentials::new(
+            "key",
+            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==",
+        )
+        .unwrap();
+        let sign = credentials.sign(
+            "/0/private/AddOrder",
+            1616492376594,
+            "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25",
+        );
+        assert_eq!(
+            sign,
+            "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ=="
+        );
+
+        assert!(matches!(
+            Credentials::new("key", "not base64!"),
+            Err(Error::Parse { input, .. }) if input == "<secret>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_retries_transport_errors() {
+        use futures_util::StreamExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // the first connection is dropped without a response
+            drop(listener.accept().await.unwrap());
+
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            let body = r#"{"error":[],"result":{"XXBTZUSD":[["1.0","1.0",15,"b","l",""]],"last":"20000000000"}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let time = |secs| chrono::DateTime::from_timestamp(secs, 0).unwrap();
+        let backfill = Backfill::new("XBT/USD".parse().unwrap(), time(10), time(20))
+            .with_delay(std::time::Duration::from_millis(1));
+        let trades = Client::new()
+            .with_url(url)
+            .backfill(backfill)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].as_ref().unwrap().price.to_string(), "1.0");
+    }
+
+    #[test]
+    fn test_nonce() {
+        let nonce = Nonce::default();
+        let first = nonce.next();
+        let second = nonce.next();
+        let third = nonce.next();
+        assert!(first < second && second < third);
+    }
+}
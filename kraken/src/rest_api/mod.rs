@@ -1,2 +1,7 @@
+pub mod backfill;
+pub mod client;
 pub mod request;
 pub mod response;
+
+pub use backfill::Backfill;
+pub use client::{Client, Credentials};
@@ -1,4 +1,9 @@
-use crate::core::CurrencyPair;
+use super::response::ledgers::LedgerType;
+use crate::core::{Asset, CurrencyPair};
+use crate::message::channel::Interval;
+use crate::Error;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
 
 const KRAKEN_TRADE_HISTORY_URL: &str = "https://api.kraken.com/0/public/Trades";
 
@@ -11,40 +16,612 @@ pub struct TradeHistory {
 }
 
 impl TradeHistory {
-    fn as_uri(&self) -> String {
-        self.since.map_or_else(
-            || {
-                format!(
-                    "{}?pair={}{}",
-                    KRAKEN_TRADE_HISTORY_URL,
-                    self.pair.cryptocurrency.abrv(),
-                    self.pair.currency.alpha3(),
-                )
-            },
-            |dt| {
-                format!(
-                    "{}?pair={}{}&since={}",
-                    KRAKEN_TRADE_HISTORY_URL,
-                    self.pair.cryptocurrency.abrv(),
-                    self.pair.currency.alpha3(),
-                    dt.timestamp_nanos()
-                )
-            },
-        )
-    }
-
-    pub fn url(self) -> String {
+    // The query string of the request, `since` is sent in nanoseconds which only covers the years
+    // 1677 to 2262.
+    pub(crate) fn query(&self) -> Result<String, Error> {
+        let mut params = vec![("pair", pair(&self.pair))];
+        if let Some(since) = self.since {
+            let nanos = since
+                .timestamp_nanos_opt()
+                .ok_or_else(|| Error::parse(since.to_rfc3339(), "out of range for `since`"))?;
+            params.push(("since", nanos.to_string()));
+        }
+        Ok(query(&params))
+    }
+
+    fn as_uri(&self) -> Result<String, Error> {
+        Ok(format!("{}?{}", KRAKEN_TRADE_HISTORY_URL, self.query()?))
+    }
+
+    pub fn url(self) -> Result<String, Error> {
         self.as_uri()
     }
 }
 
-impl<B> From<TradeHistory> for http::Request<B>
+impl<B> std::convert::TryFrom<TradeHistory> for http::Request<B>
 where
     B: Default,
 {
-    fn from(hist: TradeHistory) -> Self {
-        http::Request::get(hist.as_uri())
+    type Error = Error;
+
+    fn try_from(hist: TradeHistory) -> Result<Self, Self::Error> {
+        let uri = hist.as_uri()?;
+        http::Request::get(uri.as_str())
             .body(B::default())
+            .map_err(|_| Error::parse(uri, "invalid request uri"))
+    }
+}
+
+// The REST API names pairs without the separator, e.g. "XBTUSD".
+fn pair(pair: &CurrencyPair) -> String {
+    format!("{}{}", pair.base, pair.quote)
+}
+
+fn pairs(pairs: &[CurrencyPair]) -> String {
+    pairs.iter().map(pair).collect::<Vec<_>>().join(",")
+}
+
+fn query(params: &[(&str, String)]) -> String {
+    serde_urlencoded::to_string(params).expect("query parameters are always serializable")
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct Assets {
+    // All assets when empty, e.g. "XBT".
+    #[builder(default)]
+    pub asset: Vec<String>,
+}
+
+impl Assets {
+    pub(crate) fn query(&self) -> String {
+        if self.asset.is_empty() {
+            return String::new();
+        }
+        query(&[("asset", self.asset.join(","))])
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct Ticker {
+    pub pair: Vec<CurrencyPair>,
+}
+
+impl Ticker {
+    pub(crate) fn query(&self) -> String {
+        query(&[("pair", pairs(&self.pair))])
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct Ohlc {
+    pub pair: CurrencyPair,
+
+    #[builder(setter(into, strip_option), default)]
+    pub interval: Option<Interval>,
+
+    // Only candles after `since` are returned, e.g. the `last` of the previous response.
+    #[builder(setter(into, strip_option), default)]
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl Ohlc {
+    pub(crate) fn query(&self) -> String {
+        let mut params = vec![("pair", pair(&self.pair))];
+        if let Some(interval) = self.interval {
+            params.push(("interval", interval.minutes().to_string()));
+        }
+        if let Some(since) = self.since {
+            params.push(("since", since.timestamp().to_string()));
+        }
+        query(&params)
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct Depth {
+    pub pair: CurrencyPair,
+
+    // The number of levels on each side.
+    #[builder(setter(into, strip_option), default)]
+    pub count: Option<usize>,
+}
+
+impl Depth {
+    pub(crate) fn query(&self) -> String {
+        let mut params = vec![("pair", pair(&self.pair))];
+        if let Some(count) = self.count {
+            params.push(("count", count.to_string()));
+        }
+        query(&params)
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct Spread {
+    pub pair: CurrencyPair,
+
+    // Only spreads after `since` are returned, e.g. the `last` of the previous response.
+    #[builder(setter(into, strip_option), default)]
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl Spread {
+    pub(crate) fn query(&self) -> String {
+        let mut params = vec![("pair", pair(&self.pair))];
+        if let Some(since) = self.since {
+            params.push(("since", since.timestamp().to_string()));
+        }
+        query(&params)
+    }
+}
+
+// The form parameters of a private request.
+#[derive(Debug, Default)]
+struct Params(Vec<(&'static str, String)>);
+
+impl Params {
+    fn value<V: ToString>(mut self, name: &'static str, value: Option<V>) -> Self {
+        if let Some(value) = value {
+            self.0.push((name, value.to_string()));
+        }
+        self
+    }
+
+    fn flag(self, name: &'static str, set: bool) -> Self {
+        self.value(name, if set { Some(true) } else { None })
+    }
+
+    // Lists are sent comma separated.
+    fn list<V: ToString>(self, name: &'static str, values: &[V]) -> Self {
+        let list = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.value(name, if list.is_empty() { None } else { Some(list) })
+    }
+
+    fn time(self, name: &'static str, time: Option<DateTime<Utc>>) -> Self {
+        self.value(name, time.map(|time| time.timestamp()))
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct TradeBalance {
+    // The asset the balances are expressed in, USD when unset.
+    #[builder(setter(into, strip_option), default)]
+    pub asset: Option<Asset>,
+}
+
+impl TradeBalance {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default().value("asset", self.asset.as_ref()).0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct OpenOrders {
+    // Whether to list the trades of each order.
+    #[builder(default)]
+    pub trades: bool,
+
+    #[builder(setter(into, strip_option), default)]
+    pub userref: Option<i64>,
+}
+
+impl OpenOrders {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .flag("trades", self.trades)
+            .value("userref", self.userref)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct ClosedOrders {
+    #[builder(default)]
+    pub trades: bool,
+
+    #[builder(setter(into, strip_option), default)]
+    pub userref: Option<i64>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub start: Option<DateTime<Utc>>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub end: Option<DateTime<Utc>>,
+
+    // The offset of the page, results are sent newest first.
+    #[builder(setter(into, strip_option), default)]
+    pub ofs: Option<u64>,
+}
+
+impl ClosedOrders {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .flag("trades", self.trades)
+            .value("userref", self.userref)
+            .time("start", self.start)
+            .time("end", self.end)
+            .value("ofs", self.ofs)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct QueryOrders {
+    // At most 50 order ids.
+    pub txid: Vec<String>,
+
+    #[builder(default)]
+    pub trades: bool,
+
+    #[builder(setter(into, strip_option), default)]
+    pub userref: Option<i64>,
+}
+
+impl QueryOrders {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .list("txid", &self.txid)
+            .flag("trades", self.trades)
+            .value("userref", self.userref)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct TradesHistory {
+    // Whether to list the trades related to each position.
+    #[builder(default)]
+    pub trades: bool,
+
+    #[builder(setter(into, strip_option), default)]
+    pub start: Option<DateTime<Utc>>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub end: Option<DateTime<Utc>>,
+
+    // The offset of the page, results are sent newest first.
+    #[builder(setter(into, strip_option), default)]
+    pub ofs: Option<u64>,
+}
+
+impl TradesHistory {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .flag("trades", self.trades)
+            .time("start", self.start)
+            .time("end", self.end)
+            .value("ofs", self.ofs)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct QueryTrades {
+    // At most 20 trade ids.
+    pub txid: Vec<String>,
+
+    #[builder(default)]
+    pub trades: bool,
+}
+
+impl QueryTrades {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .list("txid", &self.txid)
+            .flag("trades", self.trades)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct OpenPositions {
+    // Every open position when empty.
+    #[builder(default)]
+    pub txid: Vec<String>,
+
+    // Whether to include the current `value` and `net` of each position.
+    #[builder(default)]
+    pub docalcs: bool,
+}
+
+impl OpenPositions {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .list("txid", &self.txid)
+            .flag("docalcs", self.docalcs)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct Ledgers {
+    // Every asset when empty.
+    #[builder(default)]
+    pub asset: Vec<Asset>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub ty: Option<LedgerType>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub start: Option<DateTime<Utc>>,
+
+    #[builder(setter(into, strip_option), default)]
+    pub end: Option<DateTime<Utc>>,
+
+    // The offset of the page, results are sent newest first.
+    #[builder(setter(into, strip_option), default)]
+    pub ofs: Option<u64>,
+}
+
+impl Ledgers {
+    // Fails for a `ty` of `LedgerType::Unknown`, leave it out to get every type.
+    pub(crate) fn params(&self) -> Result<Vec<(&'static str, String)>, Error> {
+        let ty = match self.ty {
+            Some(ty) => Some(
+                ty.as_str()
+                    .ok_or_else(|| Error::parse(format!("{:?}", ty), "not a ledger type filter"))?,
+            ),
+            None => None,
+        };
+        Ok(Params::default()
+            .list("asset", &self.asset)
+            .value("type", ty)
+            .time("start", self.start)
+            .time("end", self.end)
+            .value("ofs", self.ofs)
+            .0)
+    }
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct QueryLedgers {
+    // At most 20 ledger ids.
+    pub id: Vec<String>,
+
+    #[builder(default)]
+    pub trades: bool,
+}
+
+impl QueryLedgers {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        Params::default()
+            .list("id", &self.id)
+            .flag("trades", self.trades)
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct TradeVolume {
+    // The pairs to report fees for.
+    #[builder(default)]
+    pub pair: Vec<CurrencyPair>,
+}
+
+impl TradeVolume {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let pairs = self.pair.iter().map(pair).collect::<Vec<_>>();
+        Params::default().list("pair", &pairs).0
+    }
+}
+
+// The WebSocket order requests (e.g. `message::request::AddOrder`) double as REST requests,
+// their fields are sent as form parameters minus the `event`, `token` and `reqid` only the socket
+// uses.
+pub(crate) fn order_params<T: serde::Serialize>(
+    request: &T,
+) -> Result<Vec<(String, String)>, Error> {
+    let fields = match serde_json::to_value(request) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(value) => return Err(Error::parse(value.to_string(), "not an order request")),
+        Err(e) => return Err(Error::parse(e.to_string(), "not an order request")),
+    };
+    let mut params = Vec::new();
+    for (name, value) in fields {
+        if matches!(name.as_str(), "event" | "token" | "reqid") {
+            continue;
+        }
+        let value = match value {
+            Value::Null => continue,
+            Value::String(value) if name == "pair" => pair(&value.parse::<CurrencyPair>()?),
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        params.push((name, value));
+    }
+    Ok(params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let xbt_usd: CurrencyPair = "XBT/USD".parse().unwrap();
+        let since = DateTime::from_timestamp(1534614057, 0).unwrap();
+
+        assert_eq!(Assets::default().query(), "");
+        assert_eq!(
+            Assets::builder()
+                .asset(vec!["XBT".to_string(), "ETH".to_string()])
+                .build()
+                .query(),
+            "asset=XBT%2CETH"
+        );
+        assert_eq!(
+            Ticker::builder()
+                .pair(vec![xbt_usd.clone(), "ETH/XBT".parse().unwrap()])
+                .build()
+                .query(),
+            "pair=XBTUSD%2CETHXBT"
+        );
+        assert_eq!(
+            Ohlc::builder()
+                .pair(xbt_usd.clone())
+                .interval(Interval::FifteenMinutes)
+                .since(since)
+                .build()
+                .query(),
+            "pair=XBTUSD&interval=15&since=1534614057"
+        );
+        assert_eq!(
+            Depth::builder()
+                .pair(xbt_usd.clone())
+                .count(10usize)
+                .build()
+                .query(),
+            "pair=XBTUSD&count=10"
+        );
+        assert_eq!(
+            Spread::builder().pair(xbt_usd.clone()).build().query(),
+            "pair=XBTUSD"
+        );
+        assert_eq!(
+            TradeHistory::builder()
+                .pair(xbt_usd.clone())
+                .since(since)
+                .build()
+                .query()
+                .unwrap(),
+            "pair=XBTUSD&since=1534614057000000000"
+        );
+        let far_future = DateTime::from_timestamp(10_000_000_000, 0).unwrap();
+        assert!(matches!(
+            TradeHistory::builder()
+                .pair(xbt_usd.clone())
+                .since(far_future)
+                .build()
+                .query(),
+            Err(Error::Parse { .. })
+        ));
+
+        let request: http::Request<()> =
+            std::convert::TryFrom::try_from(TradeHistory::builder().pair(xbt_usd).build()).unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://api.kraken.com/0/public/Trades?pair=XBTUSD"
+        );
+    }
+
+    #[test]
+    fn test_order_params() {
+        use crate::message::request::add_order::{
+            self, OrderFlag, OrderFlags, OrderTime, OrderType, Side,
+        };
+        use std::collections::HashMap;
+        use std::convert::TryFrom;
+
+        let order = add_order::AddOrder::builder()
+            .token("token")
+            .reqid(7)
+            .ordertype(OrderType::Limit {
+                price: "30010.0".parse().unwrap(),
+            })
+            .ty(Side::Buy)
+            .pair("XBT/USD".parse().unwrap())
+            .volume("1.25".parse::<crate::core::KrakenFloat>().unwrap())
+            .oflags(OrderFlags::new(vec![OrderFlag::Post, OrderFlag::Fciq]).unwrap())
+            .expiretm(OrderTime::After(std::time::Duration::from_secs(60)))
+            .userref(42)
+            .close(
+                add_order::Close::try_from(OrderType::StopLoss {
+                    price: "29000.0".parse().unwrap(),
+                })
+                .unwrap(),
+            )
+            .build();
+
+        let params = order_params(&order)
             .unwrap()
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let expected = vec![
+            ("ordertype", "limit"),
+            ("price", "30010.0"),
+            ("type", "buy"),
+            ("pair", "XBTUSD"),
+            ("volume", "1.25"),
+            ("oflags", "fciq,post"),
+            ("expiretm", "+60"),
+            ("userref", "42"),
+            ("close[ordertype]", "stop-loss"),
+            ("close[price]", "29000.0"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<HashMap<_, _>>();
+        assert_eq!(params, expected);
+
+        assert!(matches!(order_params(&42), Err(Error::Parse { .. })));
+        let bad_pair = serde_json::json!({ "ordertype": "market", "pair": "XBT/US$" });
+        assert!(matches!(
+            order_params(&bad_pair),
+            Err(Error::Parse { input, .. }) if input == "XBT/US$"
+        ));
+    }
+
+    #[test]
+    fn test_params() {
+        let start = DateTime::from_timestamp(1688464484, 0).unwrap();
+
+        assert!(OpenOrders::default().params().is_empty());
+        assert_eq!(
+            ClosedOrders::builder()
+                .trades(true)
+                .start(start)
+                .ofs(50u64)
+                .build()
+                .params(),
+            vec![
+                ("trades", "true".to_string()),
+                ("start", "1688464484".to_string()),
+                ("ofs", "50".to_string())
+            ]
+        );
+        assert_eq!(
+            QueryOrders::builder()
+                .txid(vec![
+                    "OQCLML-BW3P3-BUCMWZ".to_string(),
+                    "OB5VMB-B4U2U-DK2WRW".to_string()
+                ])
+                .build()
+                .params(),
+            vec![(
+                "txid",
+                "OQCLML-BW3P3-BUCMWZ,OB5VMB-B4U2U-DK2WRW".to_string()
+            )]
+        );
+        assert_eq!(
+            Ledgers::builder()
+                .asset(vec![
+                    "XBT".parse::<Asset>().unwrap(),
+                    "USD".parse().unwrap()
+                ])
+                .ty(LedgerType::Deposit)
+                .build()
+                .params()
+                .unwrap(),
+            vec![
+                ("asset", "XBT,USD".to_string()),
+                ("type", "deposit".to_string())
+            ]
+        );
+        // a type read back as `Unknown` doesn't silently request every type
+        assert!(matches!(
+            Ledgers::builder().ty(LedgerType::Unknown).build().params(),
+            Err(Error::Parse { .. })
+        ));
+        assert_eq!(
+            TradeVolume::builder()
+                .pair(vec!["XBT/USD".parse().unwrap()])
+                .build()
+                .params(),
+            vec![("pair", "XBTUSD".to_string())]
+        );
     }
 }
@@ -1,22 +1,433 @@
-use serde::Deserialize;
+use crate::core::Asset;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum Error {
-    Ok(),
-    Err(Vec<String>),
-}
+pub mod error {
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Severity {
+        Error,
+        Warning,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Category {
+        General,
+        Api,
+        Query,
+        Order,
+        Trade,
+        Funding,
+        Service,
+        Session,
+        Database,
+        Unknown,
+    }
+
+    impl Category {
+        fn from_name(name: &str) -> Self {
+            match name {
+                "General" => Category::General,
+                "API" => Category::Api,
+                "Query" => Category::Query,
+                "Order" => Category::Order,
+                "Trade" => Category::Trade,
+                "Funding" => Category::Funding,
+                "Service" => Category::Service,
+                "Session" => Category::Session,
+                "Database" => Category::Database,
+                _ => Category::Unknown,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        // EGeneral
+        InvalidArguments,
+        PermissionDenied,
+        TemporaryLockout,
+        UnknownMethod,
+        InternalError,
+        // EAPI
+        InvalidKey,
+        InvalidSignature,
+        InvalidNonce,
+        RateLimitExceeded,
+        FeatureDisabled,
+        // EQuery
+        UnknownAssetPair,
+        UnknownAsset,
+        // EOrder
+        InsufficientFunds,
+        InsufficientMargin,
+        MarginAllowanceExceeded,
+        MarginLevelTooLow,
+        CannotOpenPosition,
+        OrderMinimumNotMet,
+        OrdersLimitExceeded,
+        OrderRateLimitExceeded,
+        PositionsLimitExceeded,
+        UnknownOrder,
+        UnknownPosition,
+        // EService
+        Unavailable,
+        Busy,
+        CancelOnly,
+        PostOnly,
+        DeadlineElapsed,
+        // ESession
+        InvalidSession,
+        Other,
+    }
+
+    impl ErrorKind {
+        fn from_parts(category: Category, message: &str) -> Self {
+            // messages may carry details after another ':', e.g. "Invalid arguments:volume"
+            let message = message.split(':').next().unwrap_or_default();
+            match (category, message) {
+                (Category::General, "Invalid arguments") => ErrorKind::InvalidArguments,
+                (Category::General, "Permission denied") => ErrorKind::PermissionDenied,
+                (Category::General, "Temporary lockout") => ErrorKind::TemporaryLockout,
+                (Category::General, "Unknown method") => ErrorKind::UnknownMethod,
+                (Category::General, "Internal error") => ErrorKind::InternalError,
+                (Category::Api, "Invalid key") => ErrorKind::InvalidKey,
+                (Category::Api, "Invalid signature") => ErrorKind::InvalidSignature,
+                (Category::Api, "Invalid nonce") => ErrorKind::InvalidNonce,
+                (Category::Api, "Rate limit exceeded") => ErrorKind::RateLimitExceeded,
+                (Category::Api, "Feature disabled") => ErrorKind::FeatureDisabled,
+                (Category::Query, "Unknown asset pair") => ErrorKind::UnknownAssetPair,
+                (Category::Query, "Unknown asset") => ErrorKind::UnknownAsset,
+                (Category::Order, "Insufficient funds") => ErrorKind::InsufficientFunds,
+                (Category::Order, "Insufficient margin") => ErrorKind::InsufficientMargin,
+                (Category::Order, "Margin allowance exceeded") => {
+                    ErrorKind::MarginAllowanceExceeded
+                }
+                (Category::Order, "Margin level too low") => ErrorKind::MarginLevelTooLow,
+                (Category::Order, "Cannot open position")
+                | (Category::Order, "Cannot open opposing position") => {
+                    ErrorKind::CannotOpenPosition
+                }
+                (Category::Order, "Order minimum not met") => ErrorKind::OrderMinimumNotMet,
+                (Category::Order, "Orders limit exceeded") => ErrorKind::OrdersLimitExceeded,
+                (Category::Order, "Rate limit exceeded") => ErrorKind::OrderRateLimitExceeded,
+                (Category::Order, "Positions limit exceeded") => ErrorKind::PositionsLimitExceeded,
+                (Category::Order, "Unknown order") => ErrorKind::UnknownOrder,
+                (Category::Order, "Unknown position") => ErrorKind::UnknownPosition,
+                (Category::Service, "Unavailable") => ErrorKind::Unavailable,
+                (Category::Service, "Busy") => ErrorKind::Busy,
+                (Category::Service, "Market in cancel_only mode") => ErrorKind::CancelOnly,
+                (Category::Service, "Market in post_only mode") => ErrorKind::PostOnly,
+                (Category::Service, "Deadline elapsed") => ErrorKind::DeadlineElapsed,
+                (Category::Session, "Invalid session") => ErrorKind::InvalidSession,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    // A single entry of a response's `error` list, e.g. "EOrder:Insufficient funds".
+    #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+    #[serde(from = "String")]
+    pub struct ApiError {
+        pub severity: Severity,
+        pub category: Category,
+        pub kind: ErrorKind,
+        code: String,
+    }
+
+    impl ApiError {
+        // The error as sent by Kraken.
+        pub fn code(&self) -> &str {
+            &self.code
+        }
+
+        // Whether the request was turned away before being processed and may succeed when sent
+        // again later, as opposed to errors that need the request, the account or the credentials
+        // to change. Safe to retry for any request, orders included.
+        pub fn is_retryable(&self) -> bool {
+            matches!(
+                self.kind,
+                ErrorKind::TemporaryLockout
+                    | ErrorKind::InvalidNonce
+                    | ErrorKind::RateLimitExceeded
+                    | ErrorKind::OrderRateLimitExceeded
+            )
+        }
+
+        // Whether the request failed on Kraken's side and may or may not have been processed,
+        // e.g. an `AddOrder` answered with "EService:Unavailable" can still have placed the order.
+        // Only idempotent requests (queries, cancellations) should be sent again blindly.
+        pub fn is_outcome_unknown(&self) -> bool {
+            match self.kind {
+                ErrorKind::InternalError
+                | ErrorKind::Unavailable
+                | ErrorKind::Busy
+                | ErrorKind::DeadlineElapsed => true,
+                ErrorKind::Other => {
+                    matches!(self.category, Category::Service | Category::Database)
+                }
+                _ => false,
+            }
+        }
+    }
+
+    impl ApiError {
+        // Parses `code` only if it is an error code of a known category, e.g.
+        // "EOrder:Insufficient funds" but not "Currency pair not supported".
+        pub fn from_code(code: &str) -> Option<Self> {
+            let error = ApiError::from(code);
+            if error.category == Category::Unknown {
+                None
+            } else {
+                Some(error)
+            }
+        }
+    }
+
+    impl From<String> for ApiError {
+        fn from(code: String) -> Self {
+            let (category, message) = match code.get(1..).and_then(|rest| rest.split_once(':')) {
+                Some((category, message)) => (Category::from_name(category), message),
+                None => (Category::Unknown, ""),
+            };
+            // only codes have a severity prefix, e.g. "WGeneral:..."
+            let severity = if code.starts_with('W') && category != Category::Unknown {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            Self {
+                severity,
+                category,
+                kind: ErrorKind::from_parts(category, message),
+                code,
+            }
+        }
+    }
+
+    impl From<&str> for ApiError {
+        fn from(code: &str) -> Self {
+            ApiError::from(code.to_string())
+        }
+    }
+
+    impl std::fmt::Display for ApiError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(&self.code)
+        }
+    }
+
+    impl std::error::Error for ApiError {}
+
+    // Why a response carries no result.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Error {
+        // The errors Kraken sent, or its warnings when it sent neither errors nor a result.
+        Api(Vec<ApiError>),
+        MissingResult,
+        // A WebSocket `errorMessage` that isn't an error code, e.g. "Currency pair not supported".
+        WebSocket(String),
+    }
+
+    impl Error {
+        pub fn errors(&self) -> &[ApiError] {
+            match self {
+                Error::Api(errors) => errors,
+                Error::MissingResult | Error::WebSocket(_) => &[],
+            }
+        }
+
+        pub fn is_retryable(&self) -> bool {
+            match self {
+                Error::Api(errors) => errors.iter().all(ApiError::is_retryable),
+                Error::MissingResult | Error::WebSocket(_) => false,
+            }
+        }
+
+        // Whether some of the errors leave the outcome unknown and the others are retryable, see
+        // `ApiError::is_outcome_unknown`.
+        pub fn is_outcome_unknown(&self) -> bool {
+            match self {
+                Error::Api(errors) => {
+                    errors.iter().any(ApiError::is_outcome_unknown)
+                        && errors
+                            .iter()
+                            .all(|error| error.is_retryable() || error.is_outcome_unknown())
+                }
+                Error::MissingResult | Error::WebSocket(_) => false,
+            }
+        }
+
+        pub fn is(&self, kind: ErrorKind) -> bool {
+            self.errors().iter().any(|error| error.kind == kind)
+        }
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Error::Api(errors) => write!(
+                    f,
+                    "{}",
+                    errors
+                        .iter()
+                        .map(ApiError::code)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Error::MissingResult => write!(f, "response has neither an error nor a result"),
+                Error::WebSocket(message) => f.write_str(message),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {}
+} // mod error
+
+pub use error::{ApiError, Category, Error, ErrorKind, Severity};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Response<R> {
-    error: Error,
-    result: Option<R>,
+    #[serde(default)]
+    pub error: Vec<ApiError>,
+    pub result: Option<R>,
+}
+
+impl<R> Response<R> {
+    // The result, unless Kraken sent an error. Warnings sent alongside a result are ignored.
+    pub fn into_result(self) -> Result<R, Error> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = self
+            .error
+            .into_iter()
+            .partition(|error| error.severity == Severity::Error);
+        if !errors.is_empty() {
+            return Err(Error::Api(errors));
+        }
+        match self.result {
+            Some(result) => Ok(result),
+            None if !warnings.is_empty() => Err(Error::Api(warnings)),
+            None => Err(Error::MissingResult),
+        }
+    }
+}
+
+// Times are sent as seconds since the epoch, with sub-second precision for trades.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "f64")]
+pub struct Timestamp(pub DateTime<Utc>);
+
+impl TryFrom<f64> for Timestamp {
+    type Error = crate::Error;
+
+    fn try_from(seconds: f64) -> Result<Self, Self::Error> {
+        let micros = (seconds * 1_000_000.0).round();
+        Some(micros)
+            .filter(|micros| micros.is_finite())
+            .and_then(|micros| DateTime::from_timestamp_micros(micros as i64))
+            .map(Timestamp)
+            .ok_or_else(|| crate::Error::parse(seconds.to_string(), "invalid Timestamp"))
+    }
+}
+
+fn rfc3339<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&time)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(serde::de::Error::custom)
+}
+
+// Start and expiry times are sent as 0 when unset.
+fn optional_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Timestamp>, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    if seconds == 0.0 {
+        return Ok(None);
+    }
+    Timestamp::try_from(seconds)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+// Private endpoints report assets by code, see `Asset::from_code`.
+fn asset<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Asset, D::Error> {
+    let code = String::deserialize(deserializer)?;
+    Asset::from_code(&code).map_err(serde::de::Error::custom)
+}
+
+fn assets<'de, D, V>(deserializer: D) -> Result<HashMap<Asset, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    HashMap::<String, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(code, value)| {
+            Asset::from_code(&code)
+                .map(|asset| (asset, value))
+                .map_err(serde::de::Error::custom)
+        })
+        .collect()
+}
+
+// The data of the single pair a response is keyed by, e.g. `{"XXBTZUSD": [...], "last": ...}`.
+#[derive(Debug, Clone)]
+pub struct PairData<T> {
+    pub pair: String,
+    pub entries: T,
+}
+
+mod pair_data {
+    use super::PairData;
+    use serde::{
+        de::{MapAccess, Visitor},
+        Deserialize, Deserializer,
+    };
+    use std::marker::PhantomData;
+
+    struct PairDataVisitor<T> {
+        marker: PhantomData<fn() -> PairData<T>>,
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for PairDataVisitor<T> {
+        type Value = PairData<T>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a single pair")
+        }
+
+        fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            match access.next_entry()? {
+                Some((pair, entries)) => Ok(PairData { pair, entries }),
+                None => Err(serde::de::Error::missing_field("pair")),
+            }
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for PairData<T> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(PairDataVisitor {
+                marker: PhantomData,
+            })
+        }
+    }
 }
 
 pub mod trade_history {
-    use serde::Deserialize;
+    use super::Timestamp;
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer};
 
-    pub type TradeData = crate::message::publication::trade::Data<f64>;
+    pub type TradeData = crate::message::publication::trade::Data<Timestamp>;
 
     #[derive(Debug, Clone)]
     pub struct Data {
@@ -65,129 +476,960 @@ pub mod trade_history {
             }
         }
 
-        impl<'de> Deserialize<'de> for Data {
-            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where
-                D: Deserializer<'de>,
-            {
-                deserializer.deserialize_map(DataVisitor::new())
-            }
-        }
+        impl<'de> Deserialize<'de> for Data {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_map(DataVisitor::new())
+            }
+        }
+    }
+
+    // `last` is sent as nanoseconds since the epoch.
+    fn nanos<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<DateTime<Utc>, D::Error> {
+        let nanos = <&str>::deserialize(deserializer)?
+            .parse::<i64>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(DateTime::from_timestamp_nanos(nanos))
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: Data,
+        // The cursor to request the next page with, see `request::TradeHistory::since`.
+        #[serde(deserialize_with = "nanos")]
+        pub last: DateTime<Utc>,
+    }
+
+    pub type TradeHistory = super::Response<Result>;
+}
+
+pub mod asset_pairs {
+    use crate::core::KrakenFloat;
+    use serde::Deserialize;
+    use std::collections::BTreeSet;
+    use std::convert::TryFrom;
+
+    // Fee (in percent) charged once the 30 day trade volume reaches `volume`.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct FeeTier {
+        pub volume: KrakenFloat,
+        pub percent: KrakenFloat,
+    }
+
+    // Fee tiers ordered by volume, sent as `[[volume, percent], ...]`.
+    #[derive(Debug, Deserialize, Clone, PartialEq)]
+    #[serde(try_from = "Vec<(u64, f64)>")]
+    pub struct FeeSchedule(Vec<FeeTier>);
+
+    impl TryFrom<Vec<(u64, f64)>> for FeeSchedule {
+        type Error = crate::Error;
+
+        fn try_from(tiers: Vec<(u64, f64)>) -> std::result::Result<Self, Self::Error> {
+            let mut tiers = tiers
+                .into_iter()
+                .map(|(volume, percent)| {
+                    Ok(FeeTier {
+                        volume: volume.into(),
+                        percent: KrakenFloat::try_from(percent)?,
+                    })
+                })
+                .collect::<std::result::Result<Vec<_>, crate::Error>>()?;
+            tiers.sort_by_key(|tier| tier.volume);
+            Ok(Self(tiers))
+        }
+    }
+
+    impl FeeSchedule {
+        pub fn tiers(&self) -> &[FeeTier] {
+            &self.0
+        }
+
+        // The tier reached with a 30 day trade volume of `volume_30d`.
+        pub fn tier(&self, volume_30d: KrakenFloat) -> Option<&FeeTier> {
+            self.0.iter().rev().find(|tier| tier.volume <= volume_30d)
+        }
+
+        // The fee (in the quote currency) for an order costing `cost`.
+        pub fn fee(&self, volume_30d: KrakenFloat, cost: KrakenFloat) -> Option<KrakenFloat> {
+            self.tier(volume_30d)?
+                .percent
+                .checked_mul(cost)?
+                .checked_div(KrakenFloat::from(100))
+        }
+    }
+
+    // Number of decimals a price or volume may be sent with.
+    #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(transparent)]
+    pub struct Precision {
+        pub decimals: u32,
+    }
+
+    impl Precision {
+        pub fn round(&self, value: KrakenFloat) -> KrakenFloat {
+            value.round_dp(self.decimals)
+        }
+
+        pub fn truncate(&self, value: KrakenFloat) -> KrakenFloat {
+            value.trunc_dp(self.decimals)
+        }
+
+        pub fn is_valid(&self, value: KrakenFloat) -> bool {
+            value.trunc_dp(self.decimals) == value
+        }
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct AssetPairInfo {
+        pub altname: String,
+        pub wsname: Option<String>,
+        pub aclass_base: String,
+        pub base: String,
+        pub aclass_quote: String,
+        pub quote: String,
+        pub lot: String,
+        pub pair_decimals: Precision,
+        pub lot_decimals: Precision,
+        pub lot_multiplier: i64,
+        pub leverage_buy: BTreeSet<u32>,
+        pub leverage_sell: BTreeSet<u32>,
+        pub fees: FeeSchedule,
+        pub fees_maker: Option<FeeSchedule>,
+        pub fee_volume_currency: String,
+        pub margin_call: i64,
+        pub margin_stop: i64,
+        pub ordermin: KrakenFloat,
+    }
+
+    impl AssetPairInfo {
+        pub fn taker_fee(&self, volume_30d: KrakenFloat, cost: KrakenFloat) -> Option<KrakenFloat> {
+            self.fees.fee(volume_30d, cost)
+        }
+
+        // Pairs without a separate maker schedule charge the taker fees.
+        pub fn maker_fee(&self, volume_30d: KrakenFloat, cost: KrakenFloat) -> Option<KrakenFloat> {
+            self.fees_maker
+                .as_ref()
+                .unwrap_or(&self.fees)
+                .fee(volume_30d, cost)
+        }
+
+        // Rounds to the nearest price the pair can be quoted at.
+        pub fn round_price(&self, price: KrakenFloat) -> KrakenFloat {
+            self.pair_decimals.round(price)
+        }
+
+        // Rounds down, so the rounded volume never exceeds what is available.
+        pub fn round_volume(&self, volume: KrakenFloat) -> KrakenFloat {
+            self.lot_decimals.truncate(volume)
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Result {
+        pub data: std::collections::HashMap<String, AssetPairInfo>,
+        // The pairs whose info couldn't be parsed, with the reason, e.g. a missing `ordermin`.
+        pub skipped: std::collections::HashMap<String, String>,
+    }
+
+    // custom Deserializer to ignore "DarkData" (e.g. AssetPairs ending with `.d`) and to keep
+    // going past pairs that don't parse
+    pub(crate) mod des {
+        use super::{AssetPairInfo, Result as Data};
+        use serde::{
+            de::{MapAccess, Visitor},
+            Deserialize, Deserializer,
+        };
+        use std::collections::HashMap;
+        use std::marker::PhantomData;
+
+        struct DataVisitor {
+            marker: PhantomData<fn() -> Data>,
+        }
+
+        impl DataVisitor {
+            fn new() -> Self {
+                Self {
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<'de> Visitor<'de> for DataVisitor {
+            type Value = Data;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("asset pair data")
+            }
+
+            fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let capacity = access.size_hint().unwrap_or(0);
+                let mut data = HashMap::with_capacity(capacity);
+                let mut skipped = HashMap::new();
+                while let Some((pair, info)) = access.next_entry::<String, serde_json::Value>()? {
+                    if pair.ends_with(".d") {
+                        continue;
+                    }
+                    match AssetPairInfo::deserialize(&info) {
+                        Ok(info) => {
+                            data.insert(pair, info);
+                        }
+                        Err(e) => {
+                            skipped.insert(pair, e.to_string());
+                        }
+                    }
+                }
+
+                if data.is_empty() && skipped.is_empty() {
+                    Err(serde::de::Error::missing_field("missing AssetPair map"))
+                } else {
+                    Ok(Data { data, skipped })
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Data {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_map(DataVisitor::new())
+            }
+        }
+    }
+
+    pub type AssetPairs = super::Response<Result>;
+}
+
+pub mod server_time {
+    use super::Timestamp;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub unixtime: Timestamp,
+        pub rfc1123: String,
+    }
+
+    pub type ServerTime = super::Response<Result>;
+}
+
+pub mod system_status {
+    use chrono::{DateTime, Utc};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Status {
+        Online,
+        Maintenance,
+        CancelOnly,
+        PostOnly,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub status: Status,
+        #[serde(deserialize_with = "super::rfc3339")]
+        pub timestamp: DateTime<Utc>,
+    }
+
+    pub type SystemStatus = super::Response<Result>;
+}
+
+pub mod assets {
+    use super::asset_pairs::Precision;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct AssetInfo {
+        pub aclass: String,
+        pub altname: String,
+        pub decimals: Precision,
+        pub display_decimals: Precision,
+        #[serde(default)]
+        pub collateral_value: Option<f64>,
+        #[serde(default)]
+        pub status: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: HashMap<String, AssetInfo>,
+    }
+
+    pub type Assets = super::Response<Result>;
+}
+
+pub mod ticker {
+    use crate::core::KrakenFloat;
+    pub use crate::message::publication::ticker::{Close, PriceHistory};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    // Unlike the websocket ticker, the whole lot volume is sent as a string.
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Level {
+        pub price: KrakenFloat,
+        pub whole_lot_volume: KrakenFloat,
+        pub lot_volume: KrakenFloat,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        #[serde(rename = "a")]
+        pub ask: Level,
+        #[serde(rename = "b")]
+        pub bid: Level,
+        #[serde(rename = "c")]
+        pub close: Close,
+        #[serde(rename = "v")]
+        pub volume: PriceHistory<KrakenFloat>,
+        #[serde(rename = "p")]
+        pub volume_weighted_average_price: PriceHistory<KrakenFloat>,
+        #[serde(rename = "t")]
+        pub number_of_trades: PriceHistory<i64>,
+        #[serde(rename = "l")]
+        pub low_price: PriceHistory<KrakenFloat>,
+        #[serde(rename = "h")]
+        pub high_price: PriceHistory<KrakenFloat>,
+        // Today's opening price only.
+        #[serde(rename = "o")]
+        pub open_price: KrakenFloat,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: HashMap<String, Data>,
+    }
+
+    pub type Ticker = super::Response<Result>;
+}
+
+pub mod ohlc {
+    use super::{PairData, Timestamp};
+    use crate::core::KrakenFloat;
+    use serde::Deserialize;
+
+    // Unlike the websocket `ohlc::Data` there is no `etime`, `time` is the start of the interval.
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        pub time: Timestamp,
+        pub open: KrakenFloat,
+        pub high: KrakenFloat,
+        pub low: KrakenFloat,
+        pub close: KrakenFloat,
+        pub vwap: KrakenFloat,
+        pub volume: KrakenFloat,
+        pub count: i64,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: PairData<Vec<Data>>,
+        // The cursor to poll for newer candles with, see `request::Ohlc::since`.
+        pub last: Timestamp,
+    }
+
+    pub type Ohlc = super::Response<Result>;
+}
+
+pub mod depth {
+    use super::Timestamp;
+    use crate::core::KrakenFloat;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct PriceLevelData {
+        pub price: KrakenFloat,
+        pub volume: KrakenFloat,
+        pub timestamp: Timestamp,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        pub asks: Vec<PriceLevelData>,
+        pub bids: Vec<PriceLevelData>,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: HashMap<String, Data>,
+    }
+
+    pub type Depth = super::Response<Result>;
+}
+
+pub mod spread {
+    use super::{PairData, Timestamp};
+    use crate::core::KrakenFloat;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Data {
+        pub time: Timestamp,
+        pub bid: KrakenFloat,
+        pub ask: KrakenFloat,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten)]
+        pub data: PairData<Vec<Data>>,
+        // The cursor to poll for newer spreads with, see `request::Spread::since`.
+        pub last: Timestamp,
+    }
+
+    pub type Spread = super::Response<Result>;
+}
+
+pub mod balance {
+    use crate::core::{Asset, KrakenFloat};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(flatten, deserialize_with = "super::assets")]
+        pub data: HashMap<Asset, KrakenFloat>,
+    }
+
+    pub type Balance = super::Response<Result>;
+}
+
+pub mod trade_balance {
+    use crate::core::KrakenFloat;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        #[serde(rename = "eb")]
+        pub equivalent_balance: KrakenFloat,
+        #[serde(rename = "tb")]
+        pub trade_balance: KrakenFloat,
+        #[serde(rename = "m")]
+        pub margin: KrakenFloat,
+        #[serde(rename = "n")]
+        pub unrealized_net_pnl: KrakenFloat,
+        #[serde(rename = "c")]
+        pub cost_basis: KrakenFloat,
+        #[serde(rename = "v")]
+        pub floating_valuation: KrakenFloat,
+        #[serde(rename = "e")]
+        pub equity: KrakenFloat,
+        #[serde(rename = "mf")]
+        pub free_margin: KrakenFloat,
+        // Only sent while positions are open.
+        #[serde(rename = "ml", default)]
+        pub margin_level: Option<KrakenFloat>,
+        #[serde(rename = "uv", default)]
+        pub unexecuted_value: Option<KrakenFloat>,
+    }
+
+    pub type TradeBalance = super::Response<Result>;
+}
+
+pub mod orders {
+    use super::Timestamp;
+    use crate::core::KrakenFloat;
+    pub use crate::message::publication::order::{OrderType, Side, Status};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Descr {
+        // e.g. "XBTUSD"
+        pub pair: String,
+        #[serde(rename = "type")]
+        pub ty: Side,
+        pub ordertype: OrderType,
+        pub price: KrakenFloat,
+        pub price2: KrakenFloat,
+        pub leverage: String,
+        pub order: String,
+        pub close: String,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct OrderInfo {
+        pub refid: Option<String>,
+        pub userref: Option<i64>,
+        pub status: Status,
+        pub opentm: Timestamp,
+        #[serde(default, deserialize_with = "super::optional_timestamp")]
+        pub starttm: Option<Timestamp>,
+        #[serde(default, deserialize_with = "super::optional_timestamp")]
+        pub expiretm: Option<Timestamp>,
+        // Only sent for closed orders.
+        #[serde(default)]
+        pub closetm: Option<Timestamp>,
+        #[serde(default)]
+        pub reason: Option<String>,
+        pub descr: Descr,
+        pub vol: KrakenFloat,
+        pub vol_exec: KrakenFloat,
+        pub cost: KrakenFloat,
+        pub fee: KrakenFloat,
+        pub price: KrakenFloat,
+        pub stopprice: KrakenFloat,
+        pub limitprice: KrakenFloat,
+        pub misc: String,
+        pub oflags: String,
+        // Only sent when requested with `trades`.
+        #[serde(default)]
+        pub trades: Vec<String>,
+    }
+}
+
+pub mod open_orders {
+    use super::orders::OrderInfo;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub open: HashMap<String, OrderInfo>,
+    }
+
+    pub type OpenOrders = super::Response<Result>;
+}
+
+pub mod closed_orders {
+    use super::orders::OrderInfo;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub closed: HashMap<String, OrderInfo>,
+        // The number of orders matching the request, of which at most 50 are sent per page.
+        pub count: u64,
+    }
+
+    pub type ClosedOrders = super::Response<Result>;
+}
+
+pub mod query_orders {
+    use super::orders::OrderInfo;
+    use std::collections::HashMap;
+
+    pub type Result = HashMap<String, OrderInfo>;
+
+    pub type QueryOrders = super::Response<Result>;
+}
+
+pub mod trades_history {
+    use super::Timestamp;
+    use crate::core::KrakenFloat;
+    pub use crate::message::publication::order::{OrderType, Side};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct TradeInfo {
+        pub ordertxid: String,
+        pub postxid: String,
+        // e.g. "XXBTZUSD"
+        pub pair: String,
+        pub time: Timestamp,
+        #[serde(rename = "type")]
+        pub ty: Side,
+        pub ordertype: OrderType,
+        pub price: KrakenFloat,
+        pub cost: KrakenFloat,
+        pub fee: KrakenFloat,
+        pub vol: KrakenFloat,
+        pub margin: KrakenFloat,
+        pub misc: String,
+        // Only sent for trades opening or closing a position.
+        #[serde(default)]
+        pub posstatus: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub trades: HashMap<String, TradeInfo>,
+        // The number of trades matching the request, of which at most 50 are sent per page.
+        pub count: u64,
+    }
+
+    pub type TradesHistory = super::Response<Result>;
+}
+
+pub mod query_trades {
+    use super::trades_history::TradeInfo;
+    use std::collections::HashMap;
+
+    pub type Result = HashMap<String, TradeInfo>;
+
+    pub type QueryTrades = super::Response<Result>;
+}
+
+pub mod open_positions {
+    use super::Timestamp;
+    use crate::core::KrakenFloat;
+    pub use crate::message::publication::order::{OrderType, Side};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct PositionInfo {
+        pub ordertxid: String,
+        pub posstatus: String,
+        // e.g. "XXBTZUSD"
+        pub pair: String,
+        pub time: Timestamp,
+        #[serde(rename = "type")]
+        pub ty: Side,
+        pub ordertype: OrderType,
+        pub cost: KrakenFloat,
+        pub fee: KrakenFloat,
+        pub vol: KrakenFloat,
+        pub vol_closed: KrakenFloat,
+        pub margin: KrakenFloat,
+        // Only sent when requested with `docalcs`.
+        #[serde(default)]
+        pub value: Option<KrakenFloat>,
+        #[serde(default)]
+        pub net: Option<KrakenFloat>,
+        pub terms: String,
+        pub rollovertm: String,
+        pub misc: String,
+        pub oflags: String,
+    }
+
+    pub type Result = HashMap<String, PositionInfo>;
+
+    pub type OpenPositions = super::Response<Result>;
+}
+
+pub mod ledgers {
+    use super::Timestamp;
+    use crate::core::{Asset, KrakenFloat};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum LedgerType {
+        Trade,
+        Deposit,
+        Withdrawal,
+        Transfer,
+        Margin,
+        Rollover,
+        Spend,
+        Receive,
+        Settled,
+        Adjustment,
+        Staking,
+        Sale,
+        Credit,
+        Dividend,
+        // Types added after this list was written.
+        #[serde(other)]
+        Unknown,
+    }
+
+    impl LedgerType {
+        // The name to filter ledgers on, `None` for `Unknown`, which isn't a type Kraken knows.
+        pub fn as_str(&self) -> Option<&'static str> {
+            let name = match self {
+                LedgerType::Trade => "trade",
+                LedgerType::Deposit => "deposit",
+                LedgerType::Withdrawal => "withdrawal",
+                LedgerType::Transfer => "transfer",
+                LedgerType::Margin => "margin",
+                LedgerType::Rollover => "rollover",
+                LedgerType::Spend => "spend",
+                LedgerType::Receive => "receive",
+                LedgerType::Settled => "settled",
+                LedgerType::Adjustment => "adjustment",
+                LedgerType::Staking => "staking",
+                LedgerType::Sale => "sale",
+                LedgerType::Credit => "credit",
+                LedgerType::Dividend => "dividend",
+                LedgerType::Unknown => return None,
+            };
+            Some(name)
+        }
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct LedgerEntry {
+        pub refid: String,
+        pub time: Timestamp,
+        #[serde(rename = "type")]
+        pub ty: LedgerType,
+        #[serde(default)]
+        pub subtype: String,
+        pub aclass: String,
+        #[serde(deserialize_with = "super::asset")]
+        pub asset: Asset,
+        pub amount: KrakenFloat,
+        pub fee: KrakenFloat,
+        pub balance: KrakenFloat,
+    }
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub ledger: HashMap<String, LedgerEntry>,
+        // The number of entries matching the request, of which at most 50 are sent per page.
+        pub count: u64,
+    }
+
+    pub type Ledgers = super::Response<Result>;
+}
+
+pub mod query_ledgers {
+    use super::ledgers::LedgerEntry;
+    use std::collections::HashMap;
+
+    pub type Result = HashMap<String, LedgerEntry>;
+
+    pub type QueryLedgers = super::Response<Result>;
+}
+
+pub mod trade_volume {
+    use crate::core::{Asset, KrakenFloat};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    // Fees (in percent) of a pair at the current 30 day volume.
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct FeeInfo {
+        pub fee: KrakenFloat,
+        pub minfee: KrakenFloat,
+        pub maxfee: KrakenFloat,
+        // Unset at the highest tier.
+        pub nextfee: Option<KrakenFloat>,
+        pub nextvolume: Option<KrakenFloat>,
+        pub tiervolume: Option<KrakenFloat>,
     }
 
     #[derive(Debug, Deserialize, Clone)]
     pub struct Result {
-        #[serde(flatten)]
-        pub data: Data,
-        pub last: String,
+        #[serde(deserialize_with = "super::asset")]
+        pub currency: Asset,
+        pub volume: KrakenFloat,
+        // Keyed by pair, e.g. "XXBTZUSD", and only sent for the requested pairs.
+        #[serde(default)]
+        pub fees: HashMap<String, FeeInfo>,
+        #[serde(default)]
+        pub fees_maker: HashMap<String, FeeInfo>,
     }
 
-    pub type TradeHistory = super::Response<Result>;
+    pub type TradeVolume = super::Response<Result>;
 }
 
-pub mod asset_pairs {
+pub mod add_order {
     use serde::Deserialize;
 
     #[derive(Debug, Deserialize, Clone)]
-    pub struct AssetPairInfo {
-        altname: String,
-        wsname: Option<String>,
-        aclass_base: String,
-        base: String,
-        aclass_quote: String,
-        quote: String,
-        lot: String,
-        pair_decimals: i64,
-        lot_decimals: i64,
-        lot_multiplier: i64,
-        leverage_buy: Vec<i64>,
-        leverage_sell: Vec<i64>,
-        fees: Vec<(i64, f64)>,
-        fees_maker: Option<Vec<(i64, f64)>>,
-        fee_volume_currency: String,
-        margin_call: i64,
-        margin_stop: i64,
-        ordermin: crate::core::KrakenFloat,
+    pub struct Descr {
+        pub order: String,
+        // Only sent for orders with a conditional close.
+        #[serde(default)]
+        pub close: Option<String>,
     }
 
     #[derive(Debug, Deserialize, Clone)]
     pub struct Result {
-        #[serde(flatten, deserialize_with = "des::deserialize_asset_pair_info")]
-        pub data: std::collections::HashMap<String, AssetPairInfo>,
+        pub descr: Descr,
+        // Empty when the order was only validated.
+        #[serde(default)]
+        pub txid: Vec<String>,
     }
 
-    // custom Deserializer to ignore "DarkData" (e.g. AssetPairs ending with `.d`)
-    pub(crate) mod des {
-        type Data = std::collections::HashMap<String, super::AssetPairInfo>;
-        use serde::{
-            de::{MapAccess, Visitor},
-            Deserializer,
-        };
-        use std::marker::PhantomData;
+    pub type AddOrder = super::Response<Result>;
+}
 
-        struct DataVisitor {
-            marker: PhantomData<fn() -> Data>,
-        }
+pub mod cancel_order {
+    use serde::Deserialize;
 
-        impl DataVisitor {
-            fn new() -> Self {
-                Self {
-                    marker: PhantomData,
-                }
-            }
-        }
+    #[derive(Debug, Deserialize, Clone, Default)]
+    pub struct Result {
+        pub count: u64,
+        // Whether the cancellation is still pending.
+        #[serde(default)]
+        pub pending: bool,
+    }
 
-        impl<'de> Visitor<'de> for DataVisitor {
-            type Value = Data;
+    pub type CancelOrder = super::Response<Result>;
+}
 
-            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                f.write_str("trade data")
-            }
+pub mod cancel_all {
+    use serde::Deserialize;
 
-            fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
-            where
-                M: MapAccess<'de>,
-            {
-                let mut map = Data::with_capacity(access.size_hint().unwrap_or(0));
-                loop {
-                    let entry = access.next_entry::<String, super::AssetPairInfo>();
-                    match entry {
-                        Ok(None) => break,
-                        Ok(Some((pair, info))) => {
-                            if !pair.ends_with(".d") {
-                                map.insert(pair, info);
-                            }
-                        }
-                        Err(_) => {}
-                    }
-                }
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub count: u64,
+    }
 
-                if map.is_empty() {
-                    Err(serde::de::Error::missing_field("missing AssetPair map"))
-                } else {
-                    Ok(map)
-                }
-            }
-        }
+    pub type CancelAll = super::Response<Result>;
+}
 
-        pub(crate) fn deserialize_asset_pair_info<'de, D>(deserializer: D) -> Result<Data, D::Error>
-        where
-            D: Deserializer<'de>,
-        {
-            deserializer.deserialize_map(DataVisitor::new())
+pub mod cancel_all_orders_after {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer};
+
+    // `triggerTime` is sent as "0" once the timer is disabled.
+    fn trigger_time<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Option<DateTime<Utc>>, D::Error> {
+        let time = String::deserialize(deserializer)?;
+        if time == "0" {
+            return Ok(None);
         }
+        DateTime::parse_from_rfc3339(&time)
+            .map(|dt| Some(dt.with_timezone(&Utc)))
+            .map_err(serde::de::Error::custom)
     }
 
-    pub type AssetPairs = super::Response<Result>;
+    #[derive(Debug, Deserialize, Clone)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Result {
+        #[serde(deserialize_with = "super::rfc3339")]
+        pub current_time: DateTime<Utc>,
+        #[serde(deserialize_with = "trigger_time")]
+        pub trigger_time: Option<DateTime<Utc>>,
+    }
+
+    pub type CancelAllOrdersAfter = super::Response<Result>;
+}
+
+pub mod websockets_token {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Result {
+        pub token: String,
+        pub expires: i64,
+    }
+
+    pub type WebSocketsToken = super::Response<Result>;
 }
 
+pub use add_order::AddOrder;
 pub use asset_pairs::AssetPairs;
+pub use assets::Assets;
+pub use balance::Balance;
+pub use cancel_all::CancelAll;
+pub use cancel_all_orders_after::CancelAllOrdersAfter;
+pub use cancel_order::CancelOrder;
+pub use closed_orders::ClosedOrders;
+pub use depth::Depth;
+pub use ledgers::Ledgers;
+pub use ohlc::Ohlc;
+pub use open_orders::OpenOrders;
+pub use open_positions::OpenPositions;
+pub use query_ledgers::QueryLedgers;
+pub use query_orders::QueryOrders;
+pub use query_trades::QueryTrades;
+pub use server_time::ServerTime;
+pub use spread::Spread;
+pub use system_status::SystemStatus;
+pub use ticker::Ticker;
+pub use trade_balance::TradeBalance;
 pub use trade_history::TradeHistory;
+pub use trade_volume::TradeVolume;
+pub use trades_history::TradesHistory;
+pub use websockets_token::WebSocketsToken;
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_error() {
+        let error = ApiError::from("EGeneral:Invalid arguments:volume");
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.category, Category::General);
+        assert_eq!(error.kind, ErrorKind::InvalidArguments);
+        assert_eq!(error.to_string(), "EGeneral:Invalid arguments:volume");
+        assert!(!error.is_retryable());
+
+        let error = ApiError::from("EAPI:Rate limit exceeded");
+        assert_eq!(error.category, Category::Api);
+        assert_eq!(error.kind, ErrorKind::RateLimitExceeded);
+        assert!(error.is_retryable());
+
+        assert_eq!(
+            ApiError::from("EOrder:Rate limit exceeded").kind,
+            ErrorKind::OrderRateLimitExceeded
+        );
+        assert_eq!(
+            ApiError::from("EService:Market in cancel_only mode").kind,
+            ErrorKind::CancelOnly
+        );
+        assert!(!ApiError::from("EService:Market in cancel_only mode").is_retryable());
+
+        // an order may have been placed despite these
+        let error = ApiError::from("EService:Unavailable");
+        assert!(!error.is_retryable());
+        assert!(error.is_outcome_unknown());
+        assert!(ApiError::from("EGeneral:Internal error").is_outcome_unknown());
+
+        // unknown messages keep their category
+        let error = ApiError::from("EService:Something new");
+        assert_eq!(error.kind, ErrorKind::Other);
+        assert!(error.is_outcome_unknown());
+        let error = ApiError::from("EFunding:Unknown withdraw key");
+        assert_eq!(error.category, Category::Funding);
+        assert!(!error.is_retryable());
+        assert_eq!(ApiError::from("garbage").category, Category::Unknown);
+
+        // plain WebSocket messages aren't codes
+        assert!(ApiError::from_code("EOrder:Insufficient funds").is_some());
+        assert!(ApiError::from_code("Currency pair not supported").is_none());
+        assert!(ApiError::from_code("Websocket: unsupported").is_none());
+        assert_eq!(
+            ApiError::from("Websocket: unsupported").severity,
+            Severity::Error
+        );
+
+        let payload = r#"{"error": ["EAPI:Rate limit exceeded", "EService:Busy"]}"#;
+        let error = serde_json::from_str::<WebSocketsToken>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap_err();
+        assert_eq!(error.errors().len(), 2);
+        assert!(!error.is_retryable());
+        assert!(error.is_outcome_unknown());
+        assert_eq!(error.to_string(), "EAPI:Rate limit exceeded, EService:Busy");
+
+        // warnings don't fail a response with a result
+        let payload = r#"{
+            "error": ["WGeneral:Deprecated"],
+            "result": {"token": "token", "expires": 900}
+        }"#;
+        let response = serde_json::from_str::<WebSocketsToken>(payload).unwrap();
+        assert_eq!(response.error[0].severity, Severity::Warning);
+        assert!(response.into_result().is_ok());
+
+        let payload = r#"{"error": []}"#;
+        assert_eq!(
+            serde_json::from_str::<WebSocketsToken>(payload)
+                .unwrap()
+                .into_result()
+                .unwrap_err(),
+            Error::MissingResult
+        );
+    }
+
     #[test]
     fn test_trade_history() {
         let payload = r#"{
@@ -313,6 +1555,884 @@ mod test {
             }
          }"#;
 
-        let _ = serde_json::from_str::<TradeHistory>(payload).unwrap();
+        let result = serde_json::from_str::<TradeHistory>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(result.data.pair, "XXBTZUSD");
+        assert_eq!(result.last.timestamp_nanos_opt(), Some(1559350785297011117));
+        let first = &result.data.trade_data[0];
+        assert_eq!(first.time.0.timestamp_micros(), 1559347203799800);
+        assert!(result
+            .data
+            .trade_data
+            .windows(2)
+            .all(|w| w[0].time <= w[1].time));
+    }
+
+    #[test]
+    fn test_asset_pair_info() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "altname": "XBTUSD",
+                    "wsname": "XBT/USD",
+                    "aclass_base": "currency",
+                    "base": "XXBT",
+                    "aclass_quote": "currency",
+                    "quote": "ZUSD",
+                    "lot": "unit",
+                    "pair_decimals": 1,
+                    "lot_decimals": 8,
+                    "lot_multiplier": 1,
+                    "leverage_buy": [2, 3, 4, 5],
+                    "leverage_sell": [2, 3, 4, 5],
+                    "fees": [[50000, 0.24], [0, 0.26], [100000, 0.22]],
+                    "fee_volume_currency": "ZUSD",
+                    "margin_call": 80,
+                    "margin_stop": 40,
+                    "ordermin": "0.0001"
+                },
+                "XETHZUSD": {
+                    "altname": "ETHUSD",
+                    "fees": [[0, -0.26]]
+                }
+            }
+        }"#;
+
+        let result = serde_json::from_str::<AssetPairs>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let info = &result.data["XXBTZUSD"];
+        // pairs that don't parse are reported instead of failing the others
+        assert_eq!(result.data.len(), 1);
+        assert!(result.skipped.contains_key("XETHZUSD"));
+
+        assert_eq!(info.pair_decimals.decimals, 1);
+        assert!(info.leverage_buy.contains(&5));
+        assert_eq!(info.ordermin, KrakenFloat::new(1, 4));
+
+        // tiers are sorted by volume
+        let volumes = info
+            .fees
+            .tiers()
+            .iter()
+            .map(|tier| tier.volume)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            volumes,
+            vec![
+                KrakenFloat::from(0),
+                KrakenFloat::from(50000),
+                KrakenFloat::from(100000)
+            ]
+        );
+
+        let cost = KrakenFloat::from(1000);
+        assert_eq!(
+            info.taker_fee(KrakenFloat::from(0), cost),
+            Some(KrakenFloat::new(26, 1))
+        );
+        assert_eq!(
+            info.taker_fee(KrakenFloat::from(75000), cost),
+            Some(KrakenFloat::new(24, 1))
+        );
+        // no maker schedule
+        assert_eq!(
+            info.maker_fee(KrakenFloat::from(100000), cost),
+            Some(KrakenFloat::new(22, 1))
+        );
+
+        assert_eq!(
+            info.round_price("8552.96".parse().unwrap()),
+            KrakenFloat::new(85530, 1)
+        );
+        assert_eq!(
+            info.round_volume("0.123456789".parse().unwrap()),
+            KrakenFloat::new(12345678, 8)
+        );
+        assert!(info.lot_decimals.is_valid(KrakenFloat::new(1, 8)));
+        assert!(!info.pair_decimals.is_valid(KrakenFloat::new(1, 2)));
+    }
+
+    #[test]
+    fn test_websockets_token() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "token": "1Dwc4lzSwNWOAwkMdqhssNNFhs1ed606d1WcF3XfEMw",
+                "expires": 900
+            }
+        }"#;
+
+        let token = serde_json::from_str::<WebSocketsToken>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(token.token, "1Dwc4lzSwNWOAwkMdqhssNNFhs1ed606d1WcF3XfEMw");
+        assert_eq!(token.expires, 900);
+
+        let payload = r#"{
+            "error": ["EAPI:Invalid key"]
+        }"#;
+        let errors = serde_json::from_str::<WebSocketsToken>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap_err();
+        assert_eq!(errors, Error::Api(vec![ApiError::from("EAPI:Invalid key")]));
+        assert!(errors.is(ErrorKind::InvalidKey));
+        assert!(!errors.is_retryable());
+    }
+
+    #[test]
+    fn test_server_time() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "unixtime": 1688669448,
+                "rfc1123": "Thu, 06 Jul 23 18:50:48 +0000"
+            }
+        }"#;
+
+        let time = serde_json::from_str::<ServerTime>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(time.unixtime.0.timestamp(), 1688669448);
+        assert_eq!(time.rfc1123, "Thu, 06 Jul 23 18:50:48 +0000");
+    }
+
+    #[test]
+    fn test_system_status() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "status": "cancel_only",
+                "timestamp": "2023-07-06T18:52:00Z"
+            }
+        }"#;
+
+        let status = serde_json::from_str::<SystemStatus>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(status.status, system_status::Status::CancelOnly);
+        assert_eq!(status.timestamp.timestamp(), 1688669520);
+    }
+
+    #[test]
+    fn test_assets() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBT": {
+                    "aclass": "currency",
+                    "altname": "XBT",
+                    "decimals": 10,
+                    "display_decimals": 5,
+                    "collateral_value": 1,
+                    "status": "enabled"
+                },
+                "ZUSD": {
+                    "aclass": "currency",
+                    "altname": "USD",
+                    "decimals": 4,
+                    "display_decimals": 2
+                }
+            }
+        }"#;
+
+        let assets = serde_json::from_str::<Assets>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let xbt = &assets.data["XXBT"];
+        assert_eq!(xbt.altname, "XBT");
+        assert_eq!(xbt.decimals.decimals, 10);
+        assert_eq!(xbt.collateral_value, Some(1.0));
+        assert_eq!(assets.data["ZUSD"].display_decimals.decimals, 2);
+        assert!(assets.data["ZUSD"].status.is_none());
+    }
+
+    #[test]
+    fn test_ticker() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "a": ["30300.10000", "1", "1.000"],
+                    "b": ["30300.00000", "1", "1.000"],
+                    "c": ["30303.20000", "0.00067643"],
+                    "v": ["4083.67001100", "4412.73601799"],
+                    "p": ["30706.77771", "30689.13205"],
+                    "t": [34619, 38907],
+                    "l": ["29868.30000", "29868.30000"],
+                    "h": ["31631.00000", "31631.00000"],
+                    "o": "30502.80000"
+                }
+            }
+        }"#;
+
+        let ticker = serde_json::from_str::<Ticker>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let data = &ticker.data["XXBTZUSD"];
+        assert_eq!(data.ask.price, KrakenFloat::new(303001, 1));
+        assert_eq!(data.bid.whole_lot_volume, KrakenFloat::from(1));
+        assert_eq!(data.close.lot_volume, KrakenFloat::new(67643, 8));
+        assert_eq!(data.number_of_trades.last_24_hours, 38907);
+        assert_eq!(data.open_price, KrakenFloat::new(305028, 1));
+    }
+
+    #[test]
+    fn test_ohlc() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": [
+                    [1688671200, "30306.1", "30306.2", "30305.7", "30305.7", "30306.1", "3.39243896", 23],
+                    [1688671260, "30304.5", "30304.5", "30300.0", "30300.0", "30300.7", "4.42996871", 18]
+                ],
+                "last": 1688672160
+            }
+        }"#;
+
+        let ohlc = serde_json::from_str::<Ohlc>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(ohlc.data.pair, "XXBTZUSD");
+        assert_eq!(ohlc.last.0.timestamp(), 1688672160);
+        let candle = &ohlc.data.entries[1];
+        assert_eq!(candle.time.0.timestamp(), 1688671260);
+        assert_eq!(candle.low, KrakenFloat::new(303000, 1));
+        assert_eq!(candle.volume, KrakenFloat::new(442996871, 8));
+        assert_eq!(candle.count, 18);
+    }
+
+    #[test]
+    fn test_depth() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "asks": [
+                        ["30384.10000", "2.059", 1688671659],
+                        ["30387.90000", "1.500", 1688671380]
+                    ],
+                    "bids": [
+                        ["30297.00000", "1.115", 1688671636]
+                    ]
+                }
+            }
+        }"#;
+
+        let depth = serde_json::from_str::<Depth>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let book = &depth.data["XXBTZUSD"];
+        assert_eq!(book.asks.len(), 2);
+        assert_eq!(book.asks[0].price, KrakenFloat::new(303841, 1));
+        assert_eq!(book.bids[0].volume, KrakenFloat::new(1115, 3));
+        assert_eq!(book.bids[0].timestamp.0.timestamp(), 1688671636);
+    }
+
+    #[test]
+    fn test_spread() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": [
+                    [1688671834, "30292.10000", "30297.50000"],
+                    [1688671834, "30292.10000", "30296.70000"]
+                ],
+                "last": 1688672106
+            }
+        }"#;
+
+        let spread = serde_json::from_str::<Spread>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(spread.data.pair, "XXBTZUSD");
+        assert_eq!(spread.data.entries.len(), 2);
+        assert_eq!(spread.data.entries[1].ask, KrakenFloat::new(302967, 1));
+        assert_eq!(spread.last.0.timestamp(), 1688672106);
+
+        let payload = r#"{"error": ["EQuery:Unknown asset pair"]}"#;
+        assert!(serde_json::from_str::<Spread>(payload)
+            .unwrap()
+            .into_result()
+            .is_err());
+    }
+
+    #[test]
+    fn test_balance() {
+        use crate::core::{Asset, KrakenFloat};
+        use crate::cryptocurrency::Cryptocurrency;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "ZUSD": "171288.6158",
+                "ZEUR": "504861.8946",
+                "XXBT": "1011.1908877900",
+                "XETH": "818.5500000000",
+                "XXDG": "6000.0000000000",
+                "DOT": "0.0000000000",
+                "ETH2.S": "198.3970800000"
+            }
+        }"#;
+
+        let balance = serde_json::from_str::<Balance>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let xbt = Asset::Cryptocurrency(Cryptocurrency::XBT);
+        assert_eq!(balance.data[&xbt], KrakenFloat::new(10111908877900, 10));
+        assert_eq!(
+            balance.data[&Asset::Cryptocurrency(Cryptocurrency::DOGE)],
+            KrakenFloat::from(6000)
+        );
+        assert_eq!(
+            balance.data[&"USD".parse().unwrap()],
+            KrakenFloat::new(1712886158, 4)
+        );
+        assert!(balance
+            .data
+            .contains_key(&Asset::Other("ETH2.S".to_string())));
+    }
+
+    #[test]
+    fn test_trade_balance() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "eb": "1101.3425",
+                "tb": "392.2264",
+                "m": "7.0354",
+                "n": "-10.0232",
+                "c": "21.1063",
+                "v": "31.1297",
+                "e": "382.2032",
+                "mf": "375.1678",
+                "ml": "5432.57"
+            }
+        }"#;
+
+        let balance = serde_json::from_str::<TradeBalance>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(balance.equivalent_balance, KrakenFloat::new(11013425, 4));
+        assert_eq!(balance.unrealized_net_pnl, KrakenFloat::new(-100232, 4));
+        assert_eq!(balance.margin_level, Some(KrakenFloat::new(543257, 2)));
+        assert!(balance.unexecuted_value.is_none());
+    }
+
+    #[test]
+    fn test_orders() {
+        use crate::core::KrakenFloat;
+
+        let open = r#"{
+            "error": [],
+            "result": {
+                "open": {
+                    "OQCLML-BW3P3-BUCMWZ": {
+                        "refid": null,
+                        "userref": 0,
+                        "status": "open",
+                        "opentm": 1616666559.8974,
+                        "starttm": 0,
+                        "expiretm": 0,
+                        "descr": {
+                            "pair": "XBTUSD",
+                            "type": "buy",
+                            "ordertype": "limit",
+                            "price": "30010.0",
+                            "price2": "0",
+                            "leverage": "none",
+                            "order": "buy 1.25000000 XBTUSD @ limit 30010.0",
+                            "close": ""
+                        },
+                        "vol": "1.25000000",
+                        "vol_exec": "0.37500000",
+                        "cost": "11253.7",
+                        "fee": "0.00000",
+                        "price": "30010.0",
+                        "stopprice": "0.00000",
+                        "limitprice": "0.00000",
+                        "misc": "",
+                        "oflags": "fciq",
+                        "trades": ["TCCCTY-WE2O6-P3NB37"]
+                    }
+                }
+            }
+        }"#;
+
+        let open = serde_json::from_str::<OpenOrders>(open)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let order = &open.open["OQCLML-BW3P3-BUCMWZ"];
+        assert_eq!(order.status, orders::Status::Open);
+        assert_eq!(order.opentm.0.timestamp_millis(), 1616666559897);
+        assert!(order.starttm.is_none() && order.expiretm.is_none());
+        assert_eq!(order.descr.ty, orders::Side::Buy);
+        assert_eq!(order.descr.ordertype, orders::OrderType::Limit);
+        assert_eq!(order.vol_exec, KrakenFloat::new(375, 3));
+        assert_eq!(order.trades, vec!["TCCCTY-WE2O6-P3NB37".to_string()]);
+
+        let closed = r#"{
+            "error": [],
+            "result": {
+                "closed": {
+                    "O37652-RJWRT-IMO74O": {
+                        "refid": null,
+                        "userref": 1,
+                        "status": "canceled",
+                        "reason": "User requested",
+                        "opentm": 1688148493.7708,
+                        "closetm": 1688148610.0482,
+                        "starttm": 0,
+                        "expiretm": 1688148800,
+                        "descr": {
+                            "pair": "XBTGBP",
+                            "type": "buy",
+                            "ordertype": "stop-loss-limit",
+                            "price": "23667.0",
+                            "price2": "0",
+                            "leverage": "none",
+                            "order": "buy 0.00100000 XBTGBP @ limit 23667.0",
+                            "close": ""
+                        },
+                        "vol": "0.00100000",
+                        "vol_exec": "0.00000000",
+                        "cost": "0.00000",
+                        "fee": "0.00000",
+                        "price": "0.00000",
+                        "stopprice": "0.00000",
+                        "limitprice": "0.00000",
+                        "misc": "",
+                        "oflags": "fciq"
+                    }
+                },
+                "count": 1
+            }
+        }"#;
+
+        let closed = serde_json::from_str::<ClosedOrders>(closed)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(closed.count, 1);
+        let order = &closed.closed["O37652-RJWRT-IMO74O"];
+        assert_eq!(order.status, orders::Status::Canceled);
+        assert_eq!(order.reason.as_deref(), Some("User requested"));
+        assert_eq!(order.closetm.unwrap().0.timestamp(), 1688148610);
+        assert_eq!(order.expiretm.unwrap().0.timestamp(), 1688148800);
+        assert_eq!(order.descr.ordertype, orders::OrderType::StopLossLimit);
+        assert!(order.trades.is_empty());
+
+        // `QueryOrders` is keyed by order id directly
+        let query = r#"{
+            "error": [],
+            "result": {
+                "OBCMZD-JIEE7-77TH3F": {
+                    "refid": null,
+                    "userref": null,
+                    "status": "closed",
+                    "opentm": 1688665496.7808,
+                    "closetm": 1688665499.1922,
+                    "starttm": 0,
+                    "expiretm": 0,
+                    "descr": {
+                        "pair": "XBTUSD",
+                        "type": "sell",
+                        "ordertype": "market",
+                        "price": "0",
+                        "price2": "0",
+                        "leverage": "none",
+                        "order": "sell 0.25000000 XBTUSD @ market",
+                        "close": ""
+                    },
+                    "vol": "0.25000000",
+                    "vol_exec": "0.25000000",
+                    "cost": "7500.0",
+                    "fee": "19.5",
+                    "price": "30000.0",
+                    "stopprice": "0.00000",
+                    "limitprice": "0.00000",
+                    "misc": "",
+                    "oflags": "fcib"
+                }
+            }
+        }"#;
+        let query = serde_json::from_str::<QueryOrders>(query)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let order = &query["OBCMZD-JIEE7-77TH3F"];
+        assert_eq!(order.status, orders::Status::Closed);
+        assert!(order.userref.is_none());
+        assert_eq!(order.fee, KrakenFloat::new(195, 1));
+    }
+
+    #[test]
+    fn test_trades_history() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "trades": {
+                    "THVRQM-33VKH-UCI7BS": {
+                        "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+                        "postxid": "TKH2SE-M7IF5-CFI7LT",
+                        "pair": "XXBTZUSD",
+                        "time": 1688667796.8802,
+                        "type": "buy",
+                        "ordertype": "limit",
+                        "price": "30010.00000",
+                        "cost": "600.20000",
+                        "fee": "0.00000",
+                        "vol": "0.02000000",
+                        "margin": "0.00000",
+                        "misc": ""
+                    },
+                    "TCWJEG-FL4SZ-3FKGH6": {
+                        "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+                        "postxid": "TKH2SE-M7IF5-CFI7LT",
+                        "pair": "XXBTZUSD",
+                        "time": 1688667769.6396,
+                        "type": "sell",
+                        "ordertype": "market",
+                        "price": "30010.00000",
+                        "cost": "300.10000",
+                        "fee": "0.00000",
+                        "vol": "0.01000000",
+                        "margin": "0.00000",
+                        "misc": "",
+                        "posstatus": "closed"
+                    }
+                },
+                "count": 2346
+            }
+        }"#;
+
+        let history = serde_json::from_str::<TradesHistory>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(history.count, 2346);
+        let trade = &history.trades["THVRQM-33VKH-UCI7BS"];
+        assert_eq!(trade.pair, "XXBTZUSD");
+        assert_eq!(trade.ty, trades_history::Side::Buy);
+        assert_eq!(trade.cost, KrakenFloat::new(6002, 1));
+        assert!(trade.posstatus.is_none());
+        assert_eq!(
+            history.trades["TCWJEG-FL4SZ-3FKGH6"].posstatus.as_deref(),
+            Some("closed")
+        );
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "THVRQM-33VKH-UCI7BS": {
+                    "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+                    "postxid": "TKH2SE-M7IF5-CFI7LT",
+                    "pair": "XXBTZUSD",
+                    "time": 1688667796.8802,
+                    "type": "buy",
+                    "ordertype": "limit",
+                    "price": "30010.00000",
+                    "cost": "600.20000",
+                    "fee": "0.00000",
+                    "vol": "0.02000000",
+                    "margin": "0.00000",
+                    "misc": ""
+                }
+            }
+        }"#;
+        let trades = serde_json::from_str::<QueryTrades>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(trades["THVRQM-33VKH-UCI7BS"].time.0.timestamp(), 1688667796);
+    }
+
+    #[test]
+    fn test_open_positions() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "TF5GVO-T7ZZ2-6NBKBI": {
+                    "ordertxid": "OLWNFG-LLH4R-D6SFFP",
+                    "posstatus": "open",
+                    "pair": "XXBTZUSD",
+                    "time": 1605280097.8294,
+                    "type": "buy",
+                    "ordertype": "limit",
+                    "cost": "104610.52842",
+                    "fee": "289.06565",
+                    "vol": "8.82412861",
+                    "vol_closed": "0.20200000",
+                    "margin": "20922.10568",
+                    "value": "258797.5",
+                    "net": "+154186.9728",
+                    "terms": "0.0100% per 4 hours",
+                    "rollovertm": "1616672637",
+                    "misc": "",
+                    "oflags": ""
+                }
+            }
+        }"#;
+
+        let positions = serde_json::from_str::<OpenPositions>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let position = &positions["TF5GVO-T7ZZ2-6NBKBI"];
+        assert_eq!(position.vol_closed, KrakenFloat::new(202, 3));
+        assert_eq!(position.net, Some(KrakenFloat::new(1541869728, 4)));
+        assert_eq!(position.value, Some(KrakenFloat::new(2587975, 1)));
+    }
+
+    #[test]
+    fn test_ledgers() {
+        use crate::core::{Asset, KrakenFloat};
+        use crate::cryptocurrency::Cryptocurrency;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "ledger": {
+                    "L4UESK-KG3EQ-UFO4T5": {
+                        "refid": "TJKLXX-PGMUI-4NTLXU",
+                        "time": 1688464484.1787,
+                        "type": "trade",
+                        "subtype": "",
+                        "aclass": "currency",
+                        "asset": "ZGBP",
+                        "amount": "-24.5000",
+                        "fee": "0.0490",
+                        "balance": "459567.9171"
+                    },
+                    "LMKZCZ-Z3GVL-CXKK4H": {
+                        "refid": "TBZIP2-F6QOU-TMB6FY",
+                        "time": 1688444262.8888,
+                        "type": "nfttrade",
+                        "subtype": "",
+                        "aclass": "currency",
+                        "asset": "XXBT",
+                        "amount": "0.0009868",
+                        "fee": "0.0000000",
+                        "balance": "1.0020368"
+                    }
+                },
+                "count": 2
+            }
+        }"#;
+
+        let ledgers = serde_json::from_str::<Ledgers>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(ledgers.count, 2);
+        let entry = &ledgers.ledger["L4UESK-KG3EQ-UFO4T5"];
+        assert_eq!(entry.ty, ledgers::LedgerType::Trade);
+        assert_eq!(entry.asset.name(), "GBP");
+        assert_eq!(entry.amount, KrakenFloat::new(-245, 1));
+
+        // types missing from `LedgerType` are still accepted
+        let entry = &ledgers.ledger["LMKZCZ-Z3GVL-CXKK4H"];
+        assert_eq!(entry.ty, ledgers::LedgerType::Unknown);
+        assert_eq!(entry.ty.as_str(), None);
+        assert_eq!(entry.asset, Asset::Cryptocurrency(Cryptocurrency::XBT));
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "L4UESK-KG3EQ-UFO4T5": {
+                    "refid": "TJKLXX-PGMUI-4NTLXU",
+                    "time": 1688464484.1787,
+                    "type": "deposit",
+                    "subtype": "",
+                    "aclass": "currency",
+                    "asset": "XETH",
+                    "amount": "1.0000000000",
+                    "fee": "0.0000000000",
+                    "balance": "1.0000000000"
+                }
+            }
+        }"#;
+        let entries = serde_json::from_str::<QueryLedgers>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let entry = &entries["L4UESK-KG3EQ-UFO4T5"];
+        assert_eq!(entry.ty, ledgers::LedgerType::Deposit);
+        assert_eq!(entry.asset, Asset::Cryptocurrency(Cryptocurrency::ETH));
+    }
+
+    #[test]
+    fn test_trade_volume() {
+        use crate::core::KrakenFloat;
+
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "currency": "ZUSD",
+                "volume": "200709587.4223",
+                "fees": {
+                    "XXBTZUSD": {
+                        "fee": "0.1000",
+                        "minfee": "0.1000",
+                        "maxfee": "0.2600",
+                        "nextfee": null,
+                        "nextvolume": null,
+                        "tiervolume": "10000000.0000"
+                    }
+                },
+                "fees_maker": {
+                    "XXBTZUSD": {
+                        "fee": "0.0000",
+                        "minfee": "0.0000",
+                        "maxfee": "0.1600",
+                        "nextfee": null,
+                        "nextvolume": null,
+                        "tiervolume": "10000000.0000"
+                    }
+                }
+            }
+        }"#;
+
+        let volume = serde_json::from_str::<TradeVolume>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(volume.currency.name(), "USD");
+        assert_eq!(volume.volume, KrakenFloat::new(2007095874223, 4));
+        let fees = &volume.fees["XXBTZUSD"];
+        assert_eq!(fees.fee, KrakenFloat::new(1, 1));
+        assert!(fees.nextfee.is_none());
+        assert_eq!(
+            volume.fees_maker["XXBTZUSD"].maxfee,
+            KrakenFloat::new(16, 2)
+        );
+
+        // fees are only sent for requested pairs
+        let payload = r#"{"error": [], "result": {"currency": "ZUSD", "volume": "0.0000"}}"#;
+        let volume = serde_json::from_str::<TradeVolume>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert!(volume.fees.is_empty());
+    }
+
+    #[test]
+    fn test_add_order() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "descr": {
+                    "order": "buy 1.25000000 XBTUSD @ limit 27500.0",
+                    "close": "close position @ stop loss 26000.0"
+                },
+                "txid": ["OU22CG-KLAF2-FWUDD7"]
+            }
+        }"#;
+
+        let result = serde_json::from_str::<AddOrder>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(result.descr.order, "buy 1.25000000 XBTUSD @ limit 27500.0");
+        assert!(result.descr.close.is_some());
+        assert_eq!(result.txid, vec!["OU22CG-KLAF2-FWUDD7".to_string()]);
+
+        // validated orders aren't placed
+        let payload = r#"{
+            "error": [],
+            "result": {"descr": {"order": "buy 1.25000000 XBTUSD @ limit 27500.0"}}
+        }"#;
+        let result = serde_json::from_str::<AddOrder>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert!(result.txid.is_empty());
+
+        let payload = r#"{"error": ["EOrder:Insufficient funds"]}"#;
+        let error = serde_json::from_str::<AddOrder>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap_err();
+        assert!(error.is(ErrorKind::InsufficientFunds));
+    }
+
+    #[test]
+    fn test_cancel_order() {
+        let payload = r#"{"error": [], "result": {"count": 1, "pending": true}}"#;
+        let result = serde_json::from_str::<CancelOrder>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(result.count, 1);
+        assert!(result.pending);
+
+        let payload = r#"{"error": [], "result": {"count": 4}}"#;
+        let result = serde_json::from_str::<CancelAll>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(result.count, 4);
+    }
+
+    #[test]
+    fn test_cancel_all_orders_after() {
+        let payload = r#"{
+            "error": [],
+            "result": {
+                "currentTime": "2023-03-24T17:41:56Z",
+                "triggerTime": "2023-03-24T17:42:56Z"
+            }
+        }"#;
+        let result = serde_json::from_str::<CancelAllOrdersAfter>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(
+            result.trigger_time.unwrap() - result.current_time,
+            chrono::Duration::seconds(60)
+        );
+
+        // disabled
+        let payload = r#"{
+            "error": [],
+            "result": {"currentTime": "2023-03-24T17:41:56Z", "triggerTime": "0"}
+        }"#;
+        let result = serde_json::from_str::<CancelAllOrdersAfter>(payload)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert!(result.trigger_time.is_none());
     }
 }
@@ -0,0 +1,370 @@
+use crate::core::CurrencyPair;
+use crate::message::{
+    channel::{ChannelName, Depth, Interval},
+    publication::{
+        BookSnapshot, BookUpdate, Ohlc, OpenOrders, OwnTrades, Publication, Spread, Ticker, Trade,
+    },
+    request::unsubscribe::{self, Unsubscribe, UnsubscribeFrom},
+    response::subscription_status::{self, SubscriptionStatus},
+};
+use futures_util::Stream;
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc;
+
+// A subscribed channel, as confirmed by its `SubscriptionStatus`.
+// Private channels (`ownTrades`, `openOrders`) have no channel id.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub channel_id: Option<i64>,
+    pub channel_name: String,
+    pub pair: Option<CurrencyPair>,
+    pub name: ChannelName,
+    pub depth: Option<Depth>,
+    pub interval: Option<Interval>,
+}
+
+impl Channel {
+    // `None` unless `status` echoes a subscription a `Subscribe` could have asked for, which any
+    // successful one does.
+    fn of_status(status: &SubscriptionStatus, channel_id: Option<i64>) -> Option<Self> {
+        let subscription = &status.subscription;
+        let name = *subscription.name.valid()?;
+        Some(Channel {
+            channel_id,
+            channel_name: status
+                .channel_name
+                .clone()
+                .unwrap_or_else(|| name.to_string()),
+            pair: status.pair.clone(),
+            name,
+            depth: match &subscription.depth {
+                Some(depth) => Some(*depth.valid()?),
+                None => None,
+            },
+            interval: match &subscription.interval {
+                Some(interval) => Some(*interval.valid()?),
+                None => None,
+            },
+        })
+    }
+
+    // The `Unsubscribe` removing this channel from the subscriptions replayed on reconnect.
+    pub(crate) fn unsubscribe(&self) -> Unsubscribe {
+        let subscription = unsubscribe::Subscription {
+            depth: self.depth,
+            interval: self.interval,
+            name: self.name,
+            token: None,
+        };
+        match &self.pair {
+            Some(pair) => Unsubscribe::builder()
+                .from(UnsubscribeFrom::Pair(vec![pair.clone()]))
+                .subscription(subscription)
+                .build(),
+            None => Unsubscribe::builder().subscription(subscription).build(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Id(i64),
+    Name(String),
+}
+
+impl Key {
+    fn of_status(status: &SubscriptionStatus) -> Option<Self> {
+        match (&status.result, &status.channel_name) {
+            (Some(subscription_status::Result::ChannelId(id)), _) => Some(Key::Id(*id)),
+            (_, Some(channel_name)) => Some(Key::Name(channel_name.clone())),
+            _ => None,
+        }
+    }
+
+    fn of_publication(publication: &Publication) -> Option<Self> {
+        match publication.channel_id() {
+            Some(id) => Some(Key::Id(id)),
+            None => publication
+                .channel_name()
+                .map(|channel_name| Key::Name(channel_name.to_string())),
+        }
+    }
+}
+
+// Maps channels to what they were subscribed to and routes their publications to the stream of
+// the `Subscribe` that created them, matched by its `reqid`.
+// Channel ids are reassigned on every connection (or book resync), so streams are only dropped
+// once their subscription is unsubscribed by the user, and picked up again whenever the replayed
+// subscription is confirmed.
+#[derive(Debug, Default)]
+pub(crate) struct Channels {
+    channels: HashMap<Key, (Channel, Option<i64>)>,
+    routes: HashMap<i64, mpsc::UnboundedSender<Publication>>,
+}
+
+impl Channels {
+    pub(crate) fn route(&mut self, reqid: i64, publications: mpsc::UnboundedSender<Publication>) {
+        self.routes.insert(reqid, publications);
+    }
+
+    // Ends the stream of the subscription sent with `reqid`.
+    pub(crate) fn unroute(&mut self, reqid: i64) {
+        self.routes.remove(&reqid);
+    }
+
+    pub(crate) fn apply(&mut self, status: &SubscriptionStatus) {
+        let key = Key::of_status(status);
+        match (status.status.as_str(), key) {
+            ("subscribed", Some(key)) => {
+                let channel_id = match key {
+                    Key::Id(id) => Some(id),
+                    Key::Name(_) => None,
+                };
+                if let Some(channel) = Channel::of_status(status, channel_id) {
+                    self.channels.insert(key, (channel, status.reqid));
+                }
+            }
+            ("unsubscribed", Some(key)) => {
+                self.channels.remove(&key);
+            }
+            // a subscription that never got a channel won't get one by waiting
+            ("error", _) => {
+                if let Some(reqid) = status.reqid {
+                    if !self
+                        .channels
+                        .values()
+                        .any(|(_, route)| *route == Some(reqid))
+                    {
+                        self.routes.remove(&reqid);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Whether the publication was passed to the stream of its subscription.
+    pub(crate) fn dispatch(&mut self, publication: &Publication) -> bool {
+        let reqid = match Key::of_publication(publication)
+            .and_then(|key| self.channels.get(&key))
+            .and_then(|(_, reqid)| *reqid)
+        {
+            Some(reqid) => reqid,
+            None => return false,
+        };
+
+        let delivered = self
+            .routes
+            .get(&reqid)
+            .map(|route| route.send(publication.clone()).is_ok());
+        if delivered == Some(false) {
+            // the stream was dropped
+            self.routes.remove(&reqid);
+        }
+        delivered == Some(true)
+    }
+
+    pub(crate) fn get(&self, channel_id: i64) -> Option<&Channel> {
+        self.channels
+            .get(&Key::Id(channel_id))
+            .map(|(channel, _)| channel)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Channel> {
+        self.channels.values().map(|(channel, _)| channel)
+    }
+
+    pub(crate) fn remove(&mut self, channel_id: i64) -> Option<Channel> {
+        self.channels
+            .remove(&Key::Id(channel_id))
+            .map(|(channel, _)| channel)
+    }
+
+    // Channel ids are only valid for the connection that assigned them.
+    pub(crate) fn reset(&mut self) {
+        self.channels.clear();
+    }
+}
+
+// A publication type a `ChannelStream` can be typed with.
+pub trait ChannelPublication: Sized + Send + 'static {
+    fn from_publication(publication: Publication) -> Option<Self>;
+}
+
+impl ChannelPublication for Publication {
+    fn from_publication(publication: Publication) -> Option<Self> {
+        Some(publication)
+    }
+}
+
+macro_rules! channel_publication {
+    ($($ty:ident),*) => {
+        $(
+            impl ChannelPublication for $ty {
+                fn from_publication(publication: Publication) -> Option<Self> {
+                    match publication {
+                        Publication::$ty(p) => Some(p),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+channel_publication!(Ticker, Ohlc, Trade, Spread, OwnTrades, OpenOrders);
+
+#[derive(Debug, Clone)]
+pub enum Book {
+    Snapshot(BookSnapshot),
+    Update(BookUpdate),
+}
+
+impl ChannelPublication for Book {
+    fn from_publication(publication: Publication) -> Option<Self> {
+        match publication {
+            Publication::BookSnapshot(p) => Some(Book::Snapshot(p)),
+            Publication::BookUpdate(p) => Some(Book::Update(p)),
+            _ => None,
+        }
+    }
+}
+
+// The publications of a single subscription, see `Client::subscribe_stream`.
+// Ends once all of its pairs are unsubscribed through the `Client`.
+#[derive(Debug)]
+pub struct ChannelStream<T> {
+    publications: mpsc::UnboundedReceiver<Publication>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ChannelPublication> ChannelStream<T> {
+    pub(crate) fn new(publications: mpsc::UnboundedReceiver<Publication>) -> Self {
+        Self {
+            publications,
+            marker: PhantomData,
+        }
+    }
+
+    pub async fn next(&mut self) -> Option<T> {
+        futures_util::StreamExt::next(self).await
+    }
+}
+
+impl<T: ChannelPublication> Stream for ChannelStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.publications.poll_recv(cx) {
+                Poll::Ready(Some(publication)) => {
+                    if let Some(publication) = T::from_publication(publication) {
+                        return Poll::Ready(Some(publication));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn status(payload: &str) -> SubscriptionStatus {
+        serde_json::from_str(payload).unwrap()
+    }
+
+    fn spread(channel_id: i64) -> Publication {
+        Publication::Spread(
+            serde_json::from_str(&format!(
+                r#"[{},["5698.40000","5700.00000","1542057299.545897","1.01234567","0.98765432"],"spread","XBT/USD"]"#,
+                channel_id
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_channels() {
+        let mut channels = Channels::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut stream = ChannelStream::<Spread>::new(rx);
+        channels.route(7, tx);
+
+        channels.apply(&status(
+            r#"{"channelID":10,"channelName":"spread","event":"subscriptionStatus","pair":"XBT/USD","reqid":7,"status":"subscribed","subscription":{"name":"spread"}}"#,
+        ));
+        channels.apply(&status(
+            r#"{"channelID":11,"channelName":"spread","event":"subscriptionStatus","pair":"ETH/USD","reqid":7,"status":"subscribed","subscription":{"name":"spread"}}"#,
+        ));
+        let channel = channels.get(10).unwrap();
+        assert_eq!(channel.channel_name, "spread");
+        assert_eq!(channel.name, ChannelName::Spread);
+        assert_eq!(channel.pair, Some("XBT/USD".parse().unwrap()));
+
+        // publications of unknown channels are dropped
+        assert!(!channels.dispatch(&spread(12)));
+        assert!(channels.dispatch(&spread(10)));
+        assert_eq!(stream.next().await.unwrap().channel_id, 10);
+
+        channels.remove(10);
+        assert!(!channels.dispatch(&spread(10)));
+        assert!(channels.dispatch(&spread(11)));
+        assert_eq!(stream.next().await.unwrap().channel_id, 11);
+
+        let unsubscribe = channels.get(11).unwrap().unsubscribe();
+        assert_eq!(
+            serde_json::to_string(&unsubscribe).unwrap(),
+            r#"{"event":"unsubscribe","pair":["ETH/USD"],"subscription":{"name":"spread"}}"#
+        );
+
+        channels.apply(&status(
+            r#"{"channelID":11,"channelName":"spread","event":"subscriptionStatus","pair":"ETH/USD","reqid":8,"status":"unsubscribed","subscription":{"name":"spread"}}"#,
+        ));
+        assert_eq!(channels.iter().count(), 0);
+
+        channels.unroute(7);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_private_channels() {
+        let mut channels = Channels::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut stream = ChannelStream::<OpenOrders>::new(rx);
+        channels.route(1, tx);
+
+        channels.apply(&status(
+            r#"{"channelName":"openOrders","event":"subscriptionStatus","reqid":1,"status":"subscribed","subscription":{"name":"openOrders"}}"#,
+        ));
+        assert!(channels.iter().all(|channel| channel.channel_id.is_none()));
+
+        let open_orders =
+            r#"[[{"OGTT3Y-C6I3P-XRI6HX":{"status":"closed"}}],"openOrders",{"sequence":235}]"#;
+        channels.dispatch(&Publication::OpenOrders(
+            serde_json::from_str(open_orders).unwrap(),
+        ));
+        assert_eq!(stream.next().await.unwrap().sequence.sequence, 235);
+
+        // reconnecting keeps the stream waiting for the replayed subscription
+        channels.reset();
+        channels.dispatch(&Publication::OpenOrders(
+            serde_json::from_str(open_orders).unwrap(),
+        ));
+        channels.apply(&status(
+            r#"{"channelName":"openOrders","event":"subscriptionStatus","reqid":1,"status":"subscribed","subscription":{"name":"openOrders"}}"#,
+        ));
+        channels.dispatch(&Publication::OpenOrders(
+            serde_json::from_str(open_orders).unwrap(),
+        ));
+        assert!(stream.next().await.is_some());
+    }
+}
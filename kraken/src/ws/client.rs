@@ -0,0 +1,1090 @@
+use super::channels::{Channel, ChannelPublication, ChannelStream, Channels};
+use super::recording::{Direction, Recorder};
+use super::watchdog::{Alarm, Liveness, Watchdog};
+use crate::core::CurrencyPair;
+use crate::error::{Error, Transport};
+use crate::message::{
+    channel::{ChannelName, Depth},
+    publication::{system_status::Status, Publication},
+    request::{
+        subscribe::{self, Subscribe},
+        unsubscribe::{self, Unsubscribe, UnsubscribeFrom},
+        Ping, Request,
+    },
+    response::{order::OrderStatus, subscription_status, Response, SubscriptionStatus},
+    Message, ParseError,
+};
+use crate::order_book::{ChecksumMismatch, OrderBook, OrderBooks};
+use crate::rate_limit::{OrderAction, RateLimiter};
+use crate::rest_api::{self, response::ApiError};
+use futures_util::{SinkExt, Stream, StreamExt};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+const KRAKEN_WS_URL: &str = "wss://ws.kraken.com";
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct Config {
+    #[builder(setter(into), default = KRAKEN_WS_URL.to_string())]
+    pub url: String,
+
+    #[builder(default = Duration::from_millis(500))]
+    pub min_backoff: Duration,
+
+    #[builder(default = Duration::from_secs(30))]
+    pub max_backoff: Duration,
+
+    // How long to wait for the response to a request before giving up on it.
+    #[builder(default = Duration::from_secs(10))]
+    pub request_timeout: Duration,
+
+    // How long the connection can go without a frame before it's probed with a `Ping`.
+    #[builder(default = Duration::from_secs(5))]
+    pub ping_interval: Duration,
+
+    // How long the connection can go without a frame before it's dropped and reconnected.
+    #[builder(default = Duration::from_secs(15))]
+    pub silence_timeout: Duration,
+
+    // Used to fetch a WebSocket token on every (re)connect, which is then filled into any private
+    // request or subscription sent without one.
+    // Private subscriptions are held back until a token was fetched, public ones aren't.
+    #[builder(default, setter(strip_option))]
+    pub rest_client: Option<rest_api::Client>,
+
+    // Consulted before sending orders and cancellations, share it with the `rest_client` when
+    // trading over both.
+    #[builder(default, setter(strip_option))]
+    pub rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::builder().build()
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Connected,
+    Disconnected,
+    Message(Box<Message>),
+    Invalid { frame: String, error: ParseError },
+    BookResync(ChecksumMismatch),
+    // The round trip of a watchdog `Ping`.
+    Latency(Duration),
+    // Something the connection task recovers from by itself, e.g. failing to fetch a token.
+    Error(Error),
+}
+
+#[derive(Debug)]
+enum Command {
+    // Routes the response echoing `reqid` to `reply`, always sent right before its request.
+    Await {
+        reqid: i64,
+        reply: oneshot::Sender<Response>,
+    },
+    // Routes the publications of the subscription sent with `reqid`, sent right before it.
+    Route {
+        reqid: i64,
+        publications: mpsc::UnboundedSender<Publication>,
+    },
+    // Replaces the recorder, `None` stops recording.
+    Record(Option<Recorder>),
+    Send(Box<dyn Outbound>),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    // The `Await` of a request held back by the rate limiter until `until`, the request and every
+    // later command queue up behind it.
+    Throttled {
+        until: Instant,
+        command: Box<Command>,
+    },
+}
+
+// A type erased `Request` waiting for the connection's token before being serialized.
+trait Outbound: Send + std::fmt::Debug {
+    fn serialize(&mut self, token: Option<&str>) -> serde_json::Result<String>;
+}
+
+impl<R: Request + Send + std::fmt::Debug> Outbound for R {
+    fn serialize(&mut self, token: Option<&str>) -> serde_json::Result<String> {
+        if let (Some(slot), Some(token)) = (self.token(), token) {
+            if slot.is_none() {
+                *slot = Some(token.to_string());
+            }
+        }
+        serde_json::to_string(self)
+    }
+}
+
+// A WebSocket connection to Kraken that survives disconnects.
+// The connection lives on a background task which reconnects (with backoff) whenever the socket
+// drops or the exchange enters maintenance, and replays every active subscription once it's back.
+#[derive(Debug)]
+pub struct Client {
+    commands: mpsc::UnboundedSender<Command>,
+    events: mpsc::UnboundedReceiver<Event>,
+    books: Arc<Mutex<OrderBooks>>,
+    channels: Arc<Mutex<Channels>>,
+    liveness: Arc<Mutex<Option<Liveness>>>,
+    next_reqid: Arc<AtomicI64>,
+    request_timeout: Duration,
+    rate_limiter: Option<RateLimiter>,
+    // When the last throttled command is released, which every later request waits for too.
+    throttled_until: Mutex<Instant>,
+}
+
+impl Client {
+    // Must be called from within a tokio runtime.
+    pub fn connect(config: Config) -> Self {
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let books = Arc::new(Mutex::new(OrderBooks::default()));
+        let channels = Arc::new(Mutex::new(Channels::default()));
+        let liveness = Arc::new(Mutex::new(None));
+        let next_reqid = Arc::new(AtomicI64::new(1));
+        let request_timeout = config.request_timeout;
+        let rate_limiter = config.rate_limiter.clone();
+        let worker = Worker {
+            backoff: Backoff::new(config.min_backoff, config.max_backoff),
+            watchdog: Watchdog::new(config.ping_interval, config.silence_timeout, Instant::now()),
+            config,
+            commands: command_rx,
+            events: event_tx,
+            subscriptions: Subscriptions::default(),
+            books: books.clone(),
+            channels: channels.clone(),
+            liveness: liveness.clone(),
+            next_reqid: next_reqid.clone(),
+            recorder: None,
+            token: None,
+            token_retry: None,
+            pending: HashMap::new(),
+            throttled: VecDeque::new(),
+        };
+        tokio::spawn(worker.run());
+        Self {
+            commands,
+            events,
+            books,
+            channels,
+            liveness,
+            next_reqid,
+            request_timeout,
+            rate_limiter,
+            throttled_until: Mutex::new(Instant::now()),
+        }
+    }
+
+    // The requests below are sent right away, the returned futures resolve to Kraken's response.
+    // They can be dropped without cancelling the request.
+    // Orders held back by the `rate_limiter` are sent once it allows, whether or not their future
+    // is still around, and so are the requests made after them, so requests go out in order.
+    // A subscription to several pairs resolves to the status of the first one.
+
+    pub fn subscribe(
+        &self,
+        subscribe: Subscribe,
+    ) -> impl Future<Output = Result<SubscriptionStatus, Error>> {
+        self.request(subscribe, Command::Subscribe)
+    }
+
+    pub fn unsubscribe(
+        &self,
+        unsubscribe: Unsubscribe,
+    ) -> impl Future<Output = Result<SubscriptionStatus, Error>> {
+        self.request(unsubscribe, Command::Unsubscribe)
+    }
+
+    // Subscribes and routes the subscription's publications to their own stream instead of
+    // `Client::next`, the stream ends once all of its pairs are unsubscribed.
+    // Publications not of type `T` are skipped, use `Publication` to receive all of them.
+    pub fn subscribe_stream<T: ChannelPublication>(
+        &self,
+        mut subscribe: Subscribe,
+    ) -> impl Future<Output = Result<(SubscriptionStatus, ChannelStream<T>), Error>> {
+        let reqid = *subscribe
+            .reqid
+            .get_or_insert_with(|| self.next_reqid.fetch_add(1, Ordering::Relaxed));
+        let (publications, stream) = mpsc::unbounded_channel();
+        let routed = self.command(Command::Route {
+            reqid,
+            publications,
+        });
+        let status = self.subscribe(subscribe);
+
+        async move {
+            routed?;
+            let status = status.await?;
+            match &status.result {
+                Some(subscription_status::Result::ErrorMessage(message)) => Err(exchange(message)),
+                _ => Ok((status, ChannelStream::new(stream))),
+            }
+        }
+    }
+
+    pub fn send<R>(&self, request: R) -> impl Future<Output = Result<R::Response, Error>>
+    where
+        R: Request + Send + std::fmt::Debug + 'static,
+    {
+        self.request(request, |request| Command::Send(Box::new(request)))
+    }
+
+    // Assigns a `reqid` unless the request already has one.
+    fn request<R, F>(
+        &self,
+        mut request: R,
+        command: F,
+    ) -> impl Future<Output = Result<R::Response, Error>>
+    where
+        R: Request,
+        F: FnOnce(R) -> Command,
+    {
+        let reqid = *request
+            .reqid()
+            .get_or_insert_with(|| self.next_reqid.fetch_add(1, Ordering::Relaxed));
+        let order = request.order();
+        let (reply, response) = oneshot::channel();
+        let (awaited, command) = (Command::Await { reqid, reply }, command(request));
+        let sent = self.throttle(order.as_ref()).and_then(|wait| {
+            let now = Instant::now();
+            let mut throttled_until = self.throttled_until.lock().unwrap();
+            let awaited = if wait == Duration::default() {
+                awaited
+            } else {
+                *throttled_until = (*throttled_until).max(now + wait);
+                Command::Throttled {
+                    until: now + wait,
+                    command: Box::new(awaited),
+                }
+            };
+            self.command(awaited).and_then(|_| self.command(command))?;
+            Ok(throttled_until.saturating_duration_since(now))
+        });
+        let timeout = self.request_timeout;
+        let limiter = self.rate_limiter.clone();
+
+        async move {
+            let wait = sent?;
+            match tokio::time::timeout(timeout + wait, response).await {
+                Err(_) => Err(Error::Timeout),
+                Ok(Err(_)) => Err(Error::Transport(Transport::Disconnected)),
+                Ok(Ok(Response::Error(error))) => Err(exchange(&error.error_message)),
+                Ok(Ok(response)) => {
+                    if let Some(limiter) = &limiter {
+                        match (&order, &response) {
+                            (Some(OrderAction::Add(pair)), Response::AddOrderStatus(status)) => {
+                                if let Some(txid) = &status.txid {
+                                    limiter.placed(pair, txid);
+                                }
+                            }
+                            (
+                                Some(OrderAction::Cancel(txids)),
+                                Response::CancelOrderStatus(status),
+                            ) if matches!(status.status, OrderStatus::Ok) => {
+                                for txid in txids {
+                                    limiter.cancelled(txid);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    R::response(response.clone()).ok_or_else(|| {
+                        Error::protocol(format!("{:?}", response), ParseError::UnexpectedResponse)
+                    })
+                }
+            }
+        }
+    }
+
+    // How long `order` has to wait for the rate limiter before being sent.
+    fn throttle(&self, order: Option<&OrderAction>) -> Result<Duration, Error> {
+        match (&self.rate_limiter, order) {
+            (Some(limiter), Some(order)) => limiter.reserve_order(order, Instant::now()),
+            _ => Ok(Duration::default()),
+        }
+    }
+
+    // A copy of the locally maintained book for `pair`, if subscribed to its `book` channel.
+    pub fn order_book(&self, pair: &CurrencyPair) -> Option<OrderBook> {
+        self.books.lock().unwrap().get(pair).cloned()
+    }
+
+    // What `channel_id` was subscribed to on the current connection.
+    pub fn channel(&self, channel_id: i64) -> Option<Channel> {
+        self.channels.lock().unwrap().get(channel_id).cloned()
+    }
+
+    pub fn channels(&self) -> Vec<Channel> {
+        self.channels.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Appends every frame sent or received from now on to the JSON lines file at `path`, across
+    // reconnects, until `stop_recording`. Replaces any recording already in progress.
+    pub fn record<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Error> {
+        let recorder = Recorder::open(path)?;
+        self.command(Command::Record(Some(recorder)))
+    }
+
+    pub fn stop_recording(&self) {
+        let _ = self.command(Command::Record(None));
+    }
+
+    // The liveness of the current connection, `None` while disconnected.
+    pub fn liveness(&self) -> Option<Liveness> {
+        *self.liveness.lock().unwrap()
+    }
+
+    pub async fn next(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    fn command(&self, command: Command) -> Result<(), Error> {
+        self.commands
+            .send(command)
+            .map_err(|_| Error::Transport(Transport::Closed))
+    }
+}
+
+// Kraken's websocket errors are either error codes, e.g. "EOrder:Insufficient funds", or plain
+// messages, e.g. "Currency pair not supported".
+fn exchange(message: &str) -> Error {
+    Error::Exchange(match ApiError::from_code(message) {
+        Some(error) => rest_api::response::Error::Api(vec![error]),
+        None => rest_api::response::Error::WebSocket(message.to_string()),
+    })
+}
+
+impl Stream for Client {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+#[derive(Debug)]
+struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = std::cmp::min(self.current * 2, self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.current = self.min;
+    }
+}
+
+// Every `Subscribe` that is still (at least partially) active, so it can be replayed on reconnect.
+#[derive(Debug, Default)]
+struct Subscriptions {
+    active: Vec<Subscribe>,
+}
+
+impl Subscriptions {
+    fn insert(&mut self, subscribe: Subscribe) {
+        self.active.push(subscribe);
+    }
+
+    // Channel ids must be resolved to a pair based `Unsubscribe` first, see `Channel::unsubscribe`.
+    // Returns the reqids of the subscriptions that are no longer active at all.
+    fn remove(&mut self, unsubscribe: &Unsubscribe) -> Vec<i64> {
+        let subscription = match &unsubscribe.subscription {
+            Some(subscription) => subscription,
+            None => return Vec::new(),
+        };
+
+        let pairs = match &unsubscribe.from {
+            Some(UnsubscribeFrom::Pair(pairs)) => Some(pairs),
+            Some(UnsubscribeFrom::ChannelID(_)) => return Vec::new(),
+            None => None,
+        };
+
+        let mut removed = Vec::new();
+        self.active.retain_mut(|subscribe| {
+            let matches = subscribe.subscription.name == subscription.name
+                && subscribe.subscription.depth == subscription.depth
+                && subscribe.subscription.interval == subscription.interval;
+            if !matches {
+                return true;
+            }
+
+            let retain = match (pairs, &mut subscribe.pair) {
+                (Some(pairs), Some(subscribed)) => {
+                    subscribed.retain(|pair| !pairs.contains(pair));
+                    !subscribed.is_empty()
+                }
+                _ => false,
+            };
+            if !retain {
+                removed.extend(subscribe.reqid);
+            }
+            retain
+        });
+        removed
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Subscribe> {
+        self.active.iter()
+    }
+}
+
+fn needs_token(subscribe: &Subscribe) -> bool {
+    subscribe.subscription.name.is_private() && subscribe.subscription.token.is_none()
+}
+
+enum Exit {
+    Shutdown,
+    Reconnect,
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+// The background task owning the connection and everything that must outlive a reconnect.
+struct Worker {
+    config: Config,
+    commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::UnboundedSender<Event>,
+    subscriptions: Subscriptions,
+    backoff: Backoff,
+    books: Arc<Mutex<OrderBooks>>,
+    channels: Arc<Mutex<Channels>>,
+    liveness: Arc<Mutex<Option<Liveness>>>,
+    // Shared with the `Client` so the watchdog's pings never reuse a request's reqid.
+    next_reqid: Arc<AtomicI64>,
+    watchdog: Watchdog,
+    recorder: Option<Recorder>,
+    token: Option<String>,
+    // When to try fetching a token again after failing to.
+    token_retry: Option<Instant>,
+    pending: HashMap<i64, oneshot::Sender<Response>>,
+    // Commands waiting for the rate limiter, in the order they were made, see `Command::Throttled`.
+    throttled: VecDeque<(Instant, Command)>,
+}
+
+impl Worker {
+    async fn run(mut self) {
+        loop {
+            if let Ok((socket, _)) =
+                tokio_tungstenite::connect_async(self.config.url.as_str()).await
+            {
+                if self.fetch_token().await.is_err() {
+                    return;
+                }
+
+                if self.events.send(Event::Connected).is_err() {
+                    return;
+                }
+
+                let exit = self.session(socket).await;
+                // responses to requests sent on the dropped connection will never arrive
+                self.pending.clear();
+                self.channels.lock().unwrap().reset();
+                *self.liveness.lock().unwrap() = None;
+                match exit {
+                    Exit::Shutdown => return,
+                    Exit::Reconnect => {
+                        if self.events.send(Event::Disconnected).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            tokio::time::delay_for(self.backoff.next()).await;
+        }
+    }
+
+    async fn session(&mut self, mut socket: Socket) -> Exit {
+        self.watchdog = Watchdog::new(
+            self.config.ping_interval,
+            self.config.silence_timeout,
+            Instant::now(),
+        );
+        *self.liveness.lock().unwrap() = Some(self.watchdog.liveness());
+
+        if let Err(exit) = self.replay(&mut socket, false).await {
+            return exit;
+        }
+
+        enum Next {
+            Command(Option<Command>),
+            Frame(Option<Result<Frame, tokio_tungstenite::tungstenite::Error>>),
+            Watchdog,
+            Token,
+            Throttled,
+        }
+
+        loop {
+            let deadline = tokio::time::Instant::from_std(self.watchdog.deadline());
+            let token_retry = self.token_retry.map(tokio::time::Instant::from_std);
+            let throttled = self
+                .throttled
+                .front()
+                .map(|(until, _)| tokio::time::Instant::from_std(*until));
+            let next = tokio::select! {
+                command = self.commands.recv() => Next::Command(command),
+                frame = socket.next() => Next::Frame(frame),
+                _ = tokio::time::delay_until(deadline) => Next::Watchdog,
+                _ = tokio::time::delay_until(token_retry.unwrap_or(deadline)), if token_retry.is_some() => {
+                    Next::Token
+                }
+                _ = tokio::time::delay_until(throttled.unwrap_or(deadline)), if throttled.is_some() => {
+                    Next::Throttled
+                }
+            };
+            if let Next::Frame(Some(Ok(_))) = next {
+                self.watchdog.frame(Instant::now());
+                *self.liveness.lock().unwrap() = Some(self.watchdog.liveness());
+            }
+
+            let result = match next {
+                Next::Command(None) => {
+                    let _ = socket.close(None).await;
+                    return Exit::Shutdown;
+                }
+                Next::Command(Some(command)) => self.handle_command(&mut socket, command).await,
+                Next::Frame(Some(Ok(Frame::Text(text)))) => {
+                    self.record(Direction::Inbound, &text);
+                    self.handle_text(&mut socket, text).await
+                }
+                Next::Frame(Some(Ok(Frame::Close(_))))
+                | Next::Frame(Some(Err(_)))
+                | Next::Frame(None) => Err(Exit::Reconnect),
+                Next::Frame(Some(Ok(_))) => Ok(()),
+                Next::Watchdog => self.handle_watchdog(&mut socket).await,
+                Next::Token => match self.fetch_token().await {
+                    Ok(()) if self.token.is_some() => self.replay(&mut socket, true).await,
+                    result => result,
+                },
+                Next::Throttled => self.release(&mut socket).await,
+            };
+
+            if let Err(exit) = result {
+                return exit;
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, socket: &mut Socket, command: Command) -> Result<(), Exit> {
+        match command {
+            Command::Throttled { until, command } => {
+                self.throttled.push_back((until, *command));
+                Ok(())
+            }
+            // requests made after a throttled one mustn't overtake it
+            Command::Await { .. }
+            | Command::Send(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+                if !self.throttled.is_empty() =>
+            {
+                self.throttled.push_back((Instant::now(), command));
+                Ok(())
+            }
+            command => self.execute(socket, command).await,
+        }
+    }
+
+    // Executes the throttled commands that are due, stopping at the first that isn't.
+    async fn release(&mut self, socket: &mut Socket) -> Result<(), Exit> {
+        let now = Instant::now();
+        while let Some((until, _)) = self.throttled.front() {
+            if *until > now {
+                break;
+            }
+            if let Some((_, command)) = self.throttled.pop_front() {
+                self.execute(socket, command).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, socket: &mut Socket, command: Command) -> Result<(), Exit> {
+        let token = self.token.as_deref();
+        let text = match command {
+            Command::Await { reqid, reply } => {
+                // forget requests whose future was dropped or timed out
+                self.pending.retain(|_, reply| !reply.is_closed());
+                self.pending.insert(reqid, reply);
+                return Ok(());
+            }
+            Command::Route {
+                reqid,
+                publications,
+            } => {
+                self.channels.lock().unwrap().route(reqid, publications);
+                return Ok(());
+            }
+            Command::Record(recorder) => {
+                self.recorder = recorder;
+                return Ok(());
+            }
+            // queued by `handle_command`, never nested
+            Command::Throttled { .. } => return Ok(()),
+            Command::Send(mut request) => request.serialize(token),
+            Command::Subscribe(subscribe) => {
+                let held = self.is_held(&subscribe);
+                let text = subscribe.clone().serialize(token);
+                self.subscriptions.insert(subscribe);
+                if held {
+                    return Ok(());
+                }
+                text
+            }
+            Command::Unsubscribe(mut unsubscribe) => {
+                let mut channels = self.channels.lock().unwrap();
+                let removed = match unsubscribe.from {
+                    Some(UnsubscribeFrom::ChannelID(channel_id)) => {
+                        match channels.remove(channel_id).map(|c| c.unsubscribe()) {
+                            Some(unsubscribe) => self.subscriptions.remove(&unsubscribe),
+                            None => Vec::new(),
+                        }
+                    }
+                    _ => self.subscriptions.remove(&unsubscribe),
+                };
+                // ends the streams of subscriptions without any channel left
+                for reqid in removed {
+                    channels.unroute(reqid);
+                }
+                drop(channels);
+                unsubscribe.serialize(token)
+            }
+        };
+
+        match text {
+            Ok(text) => self.send(socket, text).await,
+            Err(_) => Ok(()),
+        }
+    }
+
+    // Fetches a WebSocket token if there's a `rest_client`.
+    // A failure is reported as `Event::Error` and retried later, without holding back the connection.
+    async fn fetch_token(&mut self) -> Result<(), Exit> {
+        let rest_client = match &self.config.rest_client {
+            Some(rest_client) => rest_client,
+            None => return Ok(()),
+        };
+        match rest_client.get_websockets_token().await {
+            Ok(result) => {
+                self.token = Some(result.token);
+                self.token_retry = None;
+                Ok(())
+            }
+            Err(error) => {
+                self.token = None;
+                // Kraken may well be down, no need to ask more often than reconnecting would
+                self.token_retry = Some(Instant::now() + self.config.max_backoff);
+                self.emit(Event::Error(error))
+            }
+        }
+    }
+
+    // Private subscriptions sent without a token wait for the connection to get one.
+    fn is_held(&self, subscribe: &Subscribe) -> bool {
+        self.token.is_none() && needs_token(subscribe)
+    }
+
+    // Sends the active subscriptions again, or only the ones that were held back for a token.
+    async fn replay(&mut self, socket: &mut Socket, held: bool) -> Result<(), Exit> {
+        // subscriptions are kept without the token, which changes with every connection
+        let token = self.token.as_deref();
+        let replay = self
+            .subscriptions
+            .iter()
+            .filter(|subscribe| !self.is_held(subscribe) && (!held || needs_token(subscribe)))
+            .filter_map(|subscribe| subscribe.clone().serialize(token).ok())
+            .collect::<Vec<_>>();
+        for text in replay {
+            self.send(socket, text).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_watchdog(&mut self, socket: &mut Socket) -> Result<(), Exit> {
+        let now = Instant::now();
+        match self.watchdog.check(now) {
+            Some(Alarm::Dead) => Err(Exit::Reconnect),
+            Some(Alarm::Ping) => {
+                let reqid = self.next_reqid.fetch_add(1, Ordering::Relaxed);
+                let ping = Ping::builder().reqid(reqid).build();
+                self.watchdog.ping_sent(reqid, now);
+                match serde_json::to_string(&ping) {
+                    Ok(text) => self.send(socket, text).await,
+                    Err(_) => Ok(()),
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn handle_text(&mut self, socket: &mut Socket, text: String) -> Result<(), Exit> {
+        let message = match Message::parse_frame(&text) {
+            Ok(message) => message,
+            Err(error) => {
+                return self.emit(Event::Invalid { frame: text, error });
+            }
+        };
+
+        // the watchdog's own pongs aren't passed on
+        if let Message::Response(Response::Pong(pong)) = &message {
+            if let Some(latency) = pong
+                .reqid
+                .and_then(|reqid| self.watchdog.pong(reqid, Instant::now()))
+            {
+                *self.liveness.lock().unwrap() = Some(self.watchdog.liveness());
+                return self.emit(Event::Latency(latency));
+            }
+        }
+
+        if let Message::Response(response) = &message {
+            if let Response::SubscriptionStatus(status) = response {
+                self.channels.lock().unwrap().apply(status);
+            }
+            if let Some(reply) = response.reqid().and_then(|id| self.pending.remove(&id)) {
+                let _ = reply.send(response.clone());
+            }
+        }
+
+        let mut resync = None;
+        let mut status = None;
+        let mut routed = false;
+        if let Message::Publication(publication) = &message {
+            match publication {
+                Publication::SystemStatus(system_status) => {
+                    status = Some(system_status.status.clone())
+                }
+                Publication::BookSnapshot(_) | Publication::BookUpdate(_) => {
+                    if let Err(mismatch) = self.books.lock().unwrap().apply(publication) {
+                        resync = Some(mismatch);
+                    }
+                }
+                _ => {}
+            }
+            routed = self.channels.lock().unwrap().dispatch(publication);
+        }
+
+        if !routed {
+            self.emit(Event::Message(Box::new(message)))?;
+        }
+
+        if let Some(mismatch) = resync {
+            self.resubscribe_book(socket, &mismatch).await?;
+            self.emit(Event::BookResync(mismatch))?;
+        }
+
+        match status {
+            Some(Status::Online) => self.backoff.reset(),
+            Some(Status::Maintenance) => return Err(Exit::Reconnect),
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    // Forces a fresh snapshot for a book that can no longer be trusted.
+    async fn resubscribe_book(
+        &mut self,
+        socket: &mut Socket,
+        mismatch: &ChecksumMismatch,
+    ) -> Result<(), Exit> {
+        // books are only ever built from the depths Kraken publishes
+        let depth = Depth::try_from(mismatch.depth).ok();
+        let unsubscribe = Unsubscribe::builder()
+            .from(UnsubscribeFrom::Pair(vec![mismatch.pair.clone()]))
+            .subscription(unsubscribe::Subscription {
+                depth,
+                ..unsubscribe::Subscription::builder()
+                    .name(ChannelName::Book)
+                    .build()
+            })
+            .build();
+        let mut subscribe = Subscribe::builder()
+            .pair(vec![mismatch.pair.clone()])
+            .subscription(subscribe::Subscription {
+                depth,
+                ..subscribe::Subscription::builder()
+                    .name(ChannelName::Book)
+                    .build()
+            })
+            .build();
+        // keeps routing the book to the stream of the original subscription
+        subscribe.reqid = self
+            .subscriptions
+            .iter()
+            .find(|active| {
+                active.subscription.name == ChannelName::Book
+                    && active
+                        .pair
+                        .as_ref()
+                        .is_some_and(|pairs| pairs.contains(&mismatch.pair))
+            })
+            .and_then(|active| active.reqid);
+
+        for text in vec![
+            serde_json::to_string(&unsubscribe),
+            serde_json::to_string(&subscribe),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            self.send(socket, text).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, socket: &mut Socket, text: String) -> Result<(), Exit> {
+        self.record(Direction::Outbound, &text);
+        socket
+            .send(Frame::text(text))
+            .await
+            .map_err(|_| Exit::Reconnect)
+    }
+
+    fn record(&mut self, direction: Direction, text: &str) {
+        if let Some(recorder) = &mut self.recorder {
+            // a recording that can't be written to is dropped rather than the connection
+            if recorder.record(direction, text).is_err() {
+                self.recorder = None;
+            }
+        }
+    }
+
+    fn emit(&self, event: Event) -> Result<(), Exit> {
+        self.events.send(event).map_err(|_| Exit::Shutdown)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::request::CancelAll;
+
+    fn pairs(pairs: &[&str]) -> Vec<CurrencyPair> {
+        pairs.iter().map(|pair| pair.parse().unwrap()).collect()
+    }
+
+    fn subscribe(name: ChannelName, pair: &[&str]) -> Subscribe {
+        Subscribe::builder()
+            .pair(pairs(pair))
+            .subscription(subscribe::Subscription::builder().name(name).build())
+            .build()
+    }
+
+    fn unsubscribe(name: ChannelName, pair: &[&str]) -> Unsubscribe {
+        Unsubscribe::builder()
+            .from(UnsubscribeFrom::Pair(pairs(pair)))
+            .subscription(unsubscribe::Subscription::builder().name(name).build())
+            .build()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(backoff.next(), Duration::from_secs(1));
+        assert_eq!(backoff.next(), Duration::from_secs(2));
+        assert_eq!(backoff.next(), Duration::from_secs(4));
+        assert_eq!(backoff.next(), Duration::from_secs(5));
+        assert_eq!(backoff.next(), Duration::from_secs(5));
+        backoff.reset();
+        assert_eq!(backoff.next(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+        let mut trade = subscribe(ChannelName::Trade, &["XBT/USD", "ETH/USD"]);
+        trade.reqid = Some(1);
+        subscriptions.insert(trade);
+        subscriptions.insert(subscribe(ChannelName::Ticker, &["XBT/USD"]));
+
+        assert!(subscriptions
+            .remove(&unsubscribe(ChannelName::Trade, &["XBT/USD"]))
+            .is_empty());
+        let active = subscriptions.iter().collect::<Vec<_>>();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].pair, Some(pairs(&["ETH/USD"])));
+
+        assert_eq!(
+            subscriptions.remove(&unsubscribe(ChannelName::Trade, &["ETH/USD"])),
+            vec![1]
+        );
+        subscriptions.remove(&unsubscribe(ChannelName::Ohlc, &["XBT/USD"]));
+        let active = subscriptions.iter().collect::<Vec<_>>();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].subscription.name, ChannelName::Ticker);
+    }
+
+    #[test]
+    fn test_token() {
+        let mut public = subscribe(ChannelName::Trade, &["XBT/USD"]);
+        let text = public.serialize(Some("token")).unwrap();
+        assert!(!text.contains("token"));
+
+        let mut private = Subscribe::builder()
+            .subscription(
+                subscribe::Subscription::builder()
+                    .name(ChannelName::OwnTrades)
+                    .build(),
+            )
+            .build();
+        let text = private.serialize(Some("token")).unwrap();
+        assert!(text.contains(r#""token":"token""#));
+
+        let mut cancel_all = CancelAll::builder().token("mine").build();
+        let text = cancel_all.serialize(Some("token")).unwrap();
+        assert!(text.contains(r#""token":"mine""#));
+    }
+
+    // A client whose commands go to the test instead of a connection task.
+    fn client(request_timeout: Duration) -> (Client, mpsc::UnboundedReceiver<Command>) {
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (_, events) = mpsc::unbounded_channel();
+        let client = Client {
+            commands,
+            events,
+            books: Arc::new(Mutex::new(OrderBooks::default())),
+            channels: Arc::new(Mutex::new(Channels::default())),
+            liveness: Arc::new(Mutex::new(None)),
+            next_reqid: Arc::new(AtomicI64::new(1)),
+            request_timeout,
+            rate_limiter: None,
+            throttled_until: Mutex::new(Instant::now()),
+        };
+        (client, command_rx)
+    }
+
+    async fn awaited(
+        commands: &mut mpsc::UnboundedReceiver<Command>,
+    ) -> (i64, oneshot::Sender<Response>) {
+        match commands.recv().await {
+            Some(Command::Await { reqid, reply }) => (reqid, reply),
+            command => panic!("expected Command::Await, got {:?}", command),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request() {
+        use crate::message::request::Ping;
+
+        let (client, mut commands) = client(Duration::from_secs(10));
+
+        // reqids are assigned unless already set
+        let pong = client.send(Ping::builder().build());
+        let (reqid, reply) = awaited(&mut commands).await;
+        assert_eq!(reqid, 1);
+        assert!(matches!(commands.recv().await, Some(Command::Send(_))));
+        reply
+            .send(serde_json::from_str(r#"{"event":"pong","reqid":1}"#).unwrap())
+            .unwrap();
+        assert_eq!(pong.await.unwrap().reqid, Some(1));
+
+        let subscribed = client.subscribe(subscribe(ChannelName::Trade, &["XBT/USD"]));
+        let (reqid, reply) = awaited(&mut commands).await;
+        assert_eq!(reqid, 2);
+        match commands.recv().await {
+            Some(Command::Subscribe(subscribe)) => assert_eq!(subscribe.reqid, Some(2)),
+            command => panic!("expected Command::Subscribe, got {:?}", command),
+        }
+        reply
+            .send(
+                serde_json::from_str(
+                    r#"{"errorMessage":"Currency pair not supported","event":"error","reqid":2}"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(matches!(
+            subscribed.await.unwrap_err(),
+            Error::Exchange(e) if e.to_string() == "Currency pair not supported"
+        ));
+
+        let pong = client.send(Ping::builder().reqid(42).build());
+        let (reqid, reply) = awaited(&mut commands).await;
+        assert_eq!(reqid, 42);
+        drop(reply);
+        assert!(matches!(
+            pong.await.unwrap_err(),
+            Error::Transport(Transport::Disconnected)
+        ));
+
+        drop(commands);
+        assert!(matches!(
+            client.send(Ping::builder().build()).await.unwrap_err(),
+            Error::Transport(Transport::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        use crate::message::request::Ping;
+
+        let (client, _commands) = client(Duration::from_millis(10));
+        assert!(matches!(
+            client.send(Ping::builder().build()).await.unwrap_err(),
+            Error::Timeout
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        use crate::message::request::{
+            add_order::{AddOrder, OrderType, Side},
+            Ping,
+        };
+        use crate::rate_limit::{Mode, Tier};
+
+        let (mut client, mut commands) = client(Duration::from_secs(10));
+        client.rate_limiter = Some(RateLimiter::new(Tier::Starter).with_mode(Mode::FailFast));
+        let order = AddOrder::builder()
+            .ordertype(OrderType::Market)
+            .ty(Side::Buy)
+            .pair("XBT/USD".parse().unwrap())
+            .volume(1)
+            .build();
+
+        let _pending = (0..60)
+            .map(|_| client.send(order.clone()))
+            .collect::<Vec<_>>();
+        for _ in 0..60 {
+            awaited(&mut commands).await;
+            assert!(matches!(commands.recv().await, Some(Command::Send(_))));
+        }
+        assert!(matches!(
+            client.send(order).await.unwrap_err(),
+            Error::RateLimited(_)
+        ));
+        // other requests aren't held back
+        let _pong = client.send(Ping::builder().build());
+        awaited(&mut commands).await;
+    }
+}
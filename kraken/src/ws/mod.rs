@@ -0,0 +1,9 @@
+pub mod channels;
+pub mod client;
+pub mod recording;
+pub mod watchdog;
+
+pub use channels::{Book, Channel, ChannelPublication, ChannelStream};
+pub use client::{Client, Config, Event};
+pub use recording::{Recorder, Replayer, Speed};
+pub use watchdog::Liveness;
@@ -0,0 +1,249 @@
+use super::client::Event;
+use crate::core::CurrencyPair;
+use crate::message::{Message, ParseError};
+use crate::order_book::{OrderBook, OrderBooks};
+use crate::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    #[serde(rename = "in")]
+    Inbound,
+    #[serde(rename = "out")]
+    Outbound,
+}
+
+// A single text frame as sent or received, one per line of a recording.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+    // Microseconds since the unix epoch.
+    pub ts: i64,
+    pub dir: Direction,
+    pub frame: String,
+}
+
+impl RecordedFrame {
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_micros(self.ts)
+    }
+}
+
+// Appends every frame of a session to a JSON lines file, see `Client::record`.
+// Each frame is flushed as soon as it's written so a crash loses nothing.
+#[derive(Debug)]
+pub struct Recorder {
+    file: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: LineWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, dir: Direction, frame: &str) -> Result<(), Error> {
+        let frame = RecordedFrame {
+            ts: Utc::now().timestamp_micros(),
+            dir,
+            frame: frame.to_string(),
+        };
+        let line = serde_json::to_string(&frame).expect("frames are always serializable");
+        Ok(writeln!(self.file, "{}", line)?)
+    }
+}
+
+// How fast a recording is replayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    // With the gaps between frames as recorded.
+    Original,
+    // With the gaps between frames divided by the factor.
+    Accelerated(f64),
+    // Without any gaps.
+    Unthrottled,
+}
+
+// Feeds the inbound frames of a recording through the same parsing and book keeping as
+// `Client`, producing the `Event`s it would have produced.
+// Outbound frames are skipped, but still count towards the timing.
+#[derive(Debug)]
+pub struct Replayer {
+    frames: VecDeque<RecordedFrame>,
+    speed: Speed,
+    last_ts: Option<i64>,
+    books: OrderBooks,
+    events: VecDeque<Event>,
+}
+
+impl Replayer {
+    pub fn new(frames: Vec<RecordedFrame>, speed: Speed) -> Self {
+        Self {
+            frames: frames.into(),
+            speed,
+            last_ts: None,
+            books: OrderBooks::default(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, speed: Speed) -> Result<Self, Error> {
+        let mut frames = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame = serde_json::from_str(&line)
+                .map_err(|e| Error::protocol(line.as_str(), ParseError::Malformed(e)))?;
+            frames.push(frame);
+        }
+        Ok(Self::new(frames, speed))
+    }
+
+    // The book for `pair` as of the frames replayed so far.
+    pub fn order_book(&self, pair: &CurrencyPair) -> Option<&OrderBook> {
+        self.books.get(pair)
+    }
+
+    pub async fn next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+
+            let frame = self.frames.pop_front()?;
+            self.wait(frame.ts).await;
+            if frame.dir == Direction::Inbound {
+                self.replay(frame.frame);
+            }
+        }
+    }
+
+    async fn wait(&mut self, ts: i64) {
+        let gap = match self.last_ts.replace(ts) {
+            Some(last_ts) => Duration::from_micros(ts.saturating_sub(last_ts).max(0) as u64),
+            None => return,
+        };
+        let gap = match self.speed {
+            Speed::Original => gap,
+            Speed::Accelerated(factor) if factor > 0.0 => gap.div_f64(factor),
+            Speed::Accelerated(_) | Speed::Unthrottled => return,
+        };
+        tokio::time::delay_for(gap).await;
+    }
+
+    fn replay(&mut self, text: String) {
+        let message = match Message::parse_frame(&text) {
+            Ok(message) => message,
+            Err(error) => {
+                self.events.push_back(Event::Invalid { frame: text, error });
+                return;
+            }
+        };
+
+        let mut resync = None;
+        if let Message::Publication(publication) = &message {
+            if let Err(mismatch) = self.books.apply(publication) {
+                resync = Some(mismatch);
+            }
+        }
+        self.events.push_back(Event::Message(Box::new(message)));
+        if let Some(mismatch) = resync {
+            self.events.push_back(Event::BookResync(mismatch));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn recording(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("kraken-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = recording("replay");
+        let frames = [
+            (
+                Direction::Inbound,
+                r#"{"connectionID":1,"event":"systemStatus","status":"online","version":"1.0.0"}"#,
+            ),
+            (
+                Direction::Outbound,
+                r#"{"event":"subscribe","pair":["XBT/USD"],"subscription":{"depth":10,"name":"book"}}"#,
+            ),
+            (
+                Direction::Inbound,
+                r#"[0,{"as":[["5541.30000","2.50700000","1534614248.123678"]],"bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-10","XBT/USD"]"#,
+            ),
+            (Direction::Inbound, "not json"),
+            (
+                Direction::Inbound,
+                r#"[0,{"a":[["5541.30000","0.00000000","1534614335.345903"]],"c":"0"},"book-10","XBT/USD"]"#,
+            ),
+        ];
+        let mut recorder = Recorder::open(&path).unwrap();
+        for (dir, frame) in frames.iter() {
+            recorder.record(*dir, frame).unwrap();
+        }
+        drop(recorder);
+
+        // recordings are appended to
+        let mut recorder = Recorder::open(&path).unwrap();
+        recorder
+            .record(Direction::Inbound, r#"{"event":"heartbeat"}"#)
+            .unwrap();
+        drop(recorder);
+
+        let mut replayer = Replayer::load(&path, Speed::Unthrottled).unwrap();
+        assert!(matches!(replayer.next().await, Some(Event::Message(_))));
+        assert!(matches!(replayer.next().await, Some(Event::Message(_))));
+        let pair = "XBT/USD".parse().unwrap();
+        assert!(replayer.order_book(&pair).is_some());
+        assert!(matches!(replayer.next().await, Some(Event::Invalid { .. })));
+        assert!(matches!(replayer.next().await, Some(Event::Message(_))));
+        match replayer.next().await {
+            Some(Event::BookResync(mismatch)) => assert_eq!(mismatch.pair, pair),
+            event => panic!("expected Event::BookResync, got {:?}", event),
+        }
+        assert!(matches!(replayer.next().await, Some(Event::Message(_))));
+        assert!(replayer.next().await.is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_speed() {
+        let frame = |ts, frame: &str| RecordedFrame {
+            ts,
+            dir: Direction::Inbound,
+            frame: frame.to_string(),
+        };
+        // a minute between frames, replayed a thousand times faster
+        let frames = vec![
+            frame(0, r#"{"event":"heartbeat"}"#),
+            frame(60_000_000, r#"{"event":"heartbeat"}"#),
+        ];
+        let mut replayer = Replayer::new(frames, Speed::Accelerated(1000.0));
+        let start = std::time::Instant::now();
+        replayer.next().await.unwrap();
+        replayer.next().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(frame(1_000_000, "").time().unwrap().timestamp(), 1);
+    }
+}
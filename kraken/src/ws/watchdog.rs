@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+
+// How healthy the current connection is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Liveness {
+    pub connected_at: Instant,
+    // The last frame of any kind, heartbeats included.
+    pub last_frame: Instant,
+    // The round trip of the last `Ping` sent by the watchdog.
+    pub latency: Option<Duration>,
+}
+
+impl Liveness {
+    pub fn since_last_frame(&self) -> Duration {
+        self.last_frame.elapsed()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Alarm {
+    // Nothing was received for `ping_interval`, probe the connection.
+    Ping,
+    // Nothing was received for `silence_timeout`, the connection is dead.
+    Dead,
+}
+
+// Tracks the frames of a single connection.
+// Kraken only sends heartbeats while subscribed, so an idle connection is probed with `Ping`s and
+// declared dead once it stays silent for `silence_timeout`.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    ping_interval: Duration,
+    silence_timeout: Duration,
+    liveness: Liveness,
+    // The reqid and send time of the unanswered ping, given up on after `ping_interval`.
+    ping: Option<(i64, Instant)>,
+}
+
+impl Watchdog {
+    pub(crate) fn new(ping_interval: Duration, silence_timeout: Duration, now: Instant) -> Self {
+        Self {
+            ping_interval,
+            silence_timeout,
+            liveness: Liveness {
+                connected_at: now,
+                last_frame: now,
+                latency: None,
+            },
+            ping: None,
+        }
+    }
+
+    pub(crate) fn liveness(&self) -> Liveness {
+        self.liveness
+    }
+
+    pub(crate) fn frame(&mut self, now: Instant) {
+        self.liveness.last_frame = now;
+    }
+
+    // When `check` has to be called next.
+    pub(crate) fn deadline(&self) -> Instant {
+        let dead = self.liveness.last_frame + self.silence_timeout;
+        std::cmp::min(self.next_ping(), dead)
+    }
+
+    // A lost pong must not stop the pings, so an unanswered one is replaced after `ping_interval`.
+    fn next_ping(&self) -> Instant {
+        let last = match self.ping {
+            Some((_, sent)) => std::cmp::max(self.liveness.last_frame, sent),
+            None => self.liveness.last_frame,
+        };
+        last + self.ping_interval
+    }
+
+    pub(crate) fn check(&self, now: Instant) -> Option<Alarm> {
+        let silence = now.saturating_duration_since(self.liveness.last_frame);
+        if silence >= self.silence_timeout {
+            Some(Alarm::Dead)
+        } else if now >= self.next_ping() {
+            Some(Alarm::Ping)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn ping_sent(&mut self, reqid: i64, now: Instant) {
+        self.ping = Some((reqid, now));
+    }
+
+    // Returns the round trip if `reqid` answers the watchdog's ping.
+    pub(crate) fn pong(&mut self, reqid: i64, now: Instant) -> Option<Duration> {
+        match self.ping {
+            Some((id, sent)) if id == reqid => {
+                self.ping = None;
+                let latency = now.saturating_duration_since(sent);
+                self.liveness.latency = Some(latency);
+                Some(latency)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_watchdog() {
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+        let mut watchdog = Watchdog::new(Duration::from_secs(5), Duration::from_secs(10), start);
+        assert_eq!(watchdog.deadline(), secs(5));
+        assert_eq!(watchdog.check(secs(4)), None);
+
+        // frames keep the connection alive
+        watchdog.frame(secs(4));
+        assert_eq!(watchdog.check(secs(8)), None);
+        assert_eq!(watchdog.check(secs(9)), Some(Alarm::Ping));
+
+        // an unanswered ping isn't repeated within the ping interval
+        watchdog.ping_sent(1, secs(9));
+        assert_eq!(watchdog.deadline(), secs(14));
+        assert_eq!(watchdog.check(secs(12)), None);
+        assert_eq!(watchdog.pong(2, secs(12)), None);
+
+        watchdog.frame(secs(12));
+        assert_eq!(watchdog.pong(1, secs(12)), Some(Duration::from_secs(3)));
+        assert_eq!(watchdog.liveness().latency, Some(Duration::from_secs(3)));
+        assert_eq!(watchdog.liveness().last_frame, secs(12));
+        assert_eq!(watchdog.deadline(), secs(17));
+
+        watchdog.ping_sent(2, secs(17));
+        assert_eq!(watchdog.check(secs(21)), None);
+        assert_eq!(watchdog.check(secs(22)), Some(Alarm::Dead));
+    }
+
+    #[test]
+    fn test_lost_pong() {
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+        let mut watchdog = Watchdog::new(Duration::from_secs(5), Duration::from_secs(30), start);
+        assert_eq!(watchdog.check(secs(5)), Some(Alarm::Ping));
+        watchdog.ping_sent(1, secs(5));
+
+        // frames keep arriving but the pong is lost
+        watchdog.frame(secs(6));
+        assert_eq!(watchdog.check(secs(10)), None);
+        assert_eq!(watchdog.deadline(), secs(11));
+        assert_eq!(watchdog.check(secs(11)), Some(Alarm::Ping));
+        watchdog.ping_sent(2, secs(11));
+
+        // the late pong of the replaced ping doesn't count
+        assert_eq!(watchdog.pong(1, secs(12)), None);
+        assert_eq!(watchdog.pong(2, secs(12)), Some(Duration::from_secs(1)));
+        assert_eq!(watchdog.liveness().latency, Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,361 @@
+use kraken::{
+    message::{
+        channel::{ChannelName, Depth},
+        publication::{Publication, Spread},
+        request::{
+            add_order::{OrderType, Side},
+            subscribe, AddOrder, Ping, Subscribe,
+        },
+        response::order::OrderStatus,
+        Message,
+    },
+    rest_api,
+    ws::{self, Event},
+    Error,
+};
+use mock_kraken::{MockServer, Reply};
+use serde_json::json;
+use std::time::Duration;
+
+fn config(server: &MockServer) -> ws::Config {
+    ws::Config::builder()
+        .url(server.url())
+        .min_backoff(Duration::from_millis(10))
+        .max_backoff(Duration::from_millis(10))
+        .request_timeout(Duration::from_millis(500))
+        .build()
+}
+
+fn subscribe(name: ChannelName, pair: &str) -> Subscribe {
+    Subscribe::builder()
+        .pair(vec![pair.parse().unwrap()])
+        .subscription(subscribe::Subscription::builder().name(name).build())
+        .build()
+}
+
+// Skips events until one matches, failing the test if none does in time.
+async fn expect<F: Fn(&Event) -> bool>(client: &mut ws::Client, matches: F) -> Event {
+    let wait = async {
+        loop {
+            match client.next().await {
+                Some(event) if matches(&event) => return event,
+                Some(_) => continue,
+                None => panic!("ws::Client stopped"),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait)
+        .await
+        .expect("timed out waiting for an event")
+}
+
+async fn connect(config: ws::Config) -> ws::Client {
+    let mut client = ws::Client::connect(config);
+    expect(&mut client, |event| matches!(event, Event::Connected)).await;
+    client
+}
+
+#[tokio::test]
+async fn test_requests() {
+    let server = MockServer::start().await.unwrap();
+    let client = connect(config(&server)).await;
+
+    let pong = client.send(Ping::builder().reqid(7).build()).await.unwrap();
+    assert_eq!(pong.reqid, Some(7));
+
+    server.script(
+        "addOrder",
+        Reply::Error("EOrder:Insufficient funds".to_string()),
+    );
+    let add_order = AddOrder::builder()
+        .token("token")
+        .ordertype(OrderType::Market)
+        .ty(Side::Buy)
+        .pair("XBT/USD".parse().unwrap())
+        .volume(1)
+        .build();
+    let status = client.send(add_order.clone()).await.unwrap();
+    assert!(matches!(status.status, OrderStatus::Error));
+    assert_eq!(
+        status.error_message.as_deref(),
+        Some("EOrder:Insufficient funds")
+    );
+    let status = client.send(add_order).await.unwrap();
+    assert!(matches!(status.status, OrderStatus::Ok));
+    assert!(status.txid.is_some());
+
+    server.script("ping", Reply::Ignore);
+    assert!(matches!(
+        client.send(Ping::builder().build()).await.unwrap_err(),
+        Error::Timeout
+    ));
+
+    server.script(
+        "subscribe",
+        Reply::Error("Currency pair not supported".to_string()),
+    );
+    assert!(matches!(
+        client
+            .subscribe_stream::<Spread>(subscribe(ChannelName::Spread, "XBT/ABC"))
+            .await
+            .unwrap_err(),
+        Error::Exchange(rest_api::response::Error::WebSocket(message))
+            if message == "Currency pair not supported"
+    ));
+}
+
+#[tokio::test]
+async fn test_channel_stream() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = connect(config(&server)).await;
+
+    let (status, mut spreads) = client
+        .subscribe_stream::<Spread>(subscribe(ChannelName::Spread, "XBT/USD"))
+        .await
+        .unwrap();
+    assert_eq!(status.status, "subscribed");
+
+    let spread = json!([
+        "5698.40000",
+        "5700.00000",
+        "1542057299.545897",
+        "1.01234567",
+        "0.98765432"
+    ]);
+    assert!(server.publish("spread", "XBT/USD", spread));
+    let spread = spreads.next().await.unwrap();
+    assert_eq!(
+        Some(spread.channel_id),
+        server.channel("spread", "XBT/USD").unwrap().id
+    );
+    assert!(client.channel(spread.channel_id).is_some());
+
+    // routed publications aren't passed to `Client::next` as well
+    assert!(server.heartbeat());
+    let event = expect(&mut client, |event| match event {
+        Event::Message(message) => matches!(
+            **message,
+            Message::Publication(Publication::Spread(_))
+                | Message::Publication(Publication::Heartbeat(_))
+        ),
+        _ => false,
+    })
+    .await;
+    assert!(matches!(
+        event,
+        Event::Message(message) if matches!(*message, Message::Publication(Publication::Heartbeat(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    let mut server = MockServer::start().await.unwrap();
+    let mut client = connect(config(&server)).await;
+
+    client
+        .subscribe(subscribe(ChannelName::Trade, "XBT/USD"))
+        .await
+        .unwrap();
+    assert_eq!(server.next_request().await.unwrap()["event"], "subscribe");
+
+    // malformed frames are reported without dropping the connection
+    assert!(server.send("not json"));
+    expect(&mut client, |event| matches!(event, Event::Invalid { .. })).await;
+
+    assert!(server.disconnect());
+    expect(&mut client, |event| matches!(event, Event::Disconnected)).await;
+    expect(&mut client, |event| matches!(event, Event::Connected)).await;
+    assert_eq!(server.connections(), 2);
+
+    // the subscription is replayed on the new connection
+    let replayed = server.next_request().await.unwrap();
+    assert_eq!(replayed["event"], "subscribe");
+    assert_eq!(replayed["pair"], json!(["XBT/USD"]));
+    expect(&mut client, |event| match event {
+        Event::Message(message) => matches!(**message, Message::Response(_)),
+        _ => false,
+    })
+    .await;
+    assert!(server.channel("trade", "XBT/USD").is_some());
+}
+
+#[tokio::test]
+async fn test_token_failure() {
+    let mut server = MockServer::start().await.unwrap();
+    let config = ws::Config {
+        rest_client: Some(rest_api::Client::new()),
+        ..config(&server)
+    };
+    let mut client = ws::Client::connect(config);
+    match expect(&mut client, |event| matches!(event, Event::Error(_))).await {
+        Event::Error(error) => assert!(matches!(error, Error::MissingCredentials)),
+        event => panic!("expected Event::Error, got {:?}", event),
+    }
+    expect(&mut client, |event| matches!(event, Event::Connected)).await;
+
+    // the private subscription waits for a token, the public one goes through
+    let own_trades = Subscribe::builder()
+        .subscription(
+            subscribe::Subscription::builder()
+                .name(ChannelName::OwnTrades)
+                .build(),
+        )
+        .build();
+    let _held = client.subscribe(own_trades);
+    let status = client
+        .subscribe(subscribe(ChannelName::Spread, "XBT/USD"))
+        .await
+        .unwrap();
+    assert_eq!(status.status, "subscribed");
+    let request = server.next_request().await.unwrap();
+    assert_eq!(request["subscription"]["name"], "spread");
+}
+
+#[tokio::test]
+async fn test_book_resync() {
+    let mut server = MockServer::start().await.unwrap();
+    let mut client = connect(config(&server)).await;
+    let pair = "XBT/USD".parse().unwrap();
+
+    let book = Subscribe::builder()
+        .pair(vec![pair])
+        .subscription(
+            subscribe::Subscription::builder()
+                .name(ChannelName::Book)
+                .depth(Depth::D10)
+                .build(),
+        )
+        .build();
+    client.subscribe(book).await.unwrap();
+    server.next_request().await.unwrap();
+
+    let snapshot = json!({
+        "as": [["5541.30000", "2.50700000", "1534614248.123678"]],
+        "bs": [["5541.20000", "1.52900000", "1534614248.765567"]],
+    });
+    assert!(server.publish("book", "XBT/USD", snapshot));
+    expect(&mut client, |event| match event {
+        Event::Message(message) => matches!(
+            **message,
+            Message::Publication(Publication::BookSnapshot(_))
+        ),
+        _ => false,
+    })
+    .await;
+    let pair = "XBT/USD".parse().unwrap();
+    assert!(client.order_book(&pair).is_some());
+
+    assert!(server.corrupt_book("XBT/USD"));
+    match expect(&mut client, |event| matches!(event, Event::BookResync(_))).await {
+        Event::BookResync(mismatch) => {
+            assert_eq!(mismatch.pair, pair);
+            assert_eq!(mismatch.depth, 10);
+        }
+        _ => unreachable!(),
+    }
+    assert!(client.order_book(&pair).is_none());
+
+    let unsubscribe = server.next_request().await.unwrap();
+    assert_eq!(unsubscribe["event"], "unsubscribe");
+    assert_eq!(
+        unsubscribe["subscription"],
+        json!({"depth": 10, "name": "book"})
+    );
+    let resubscribe = server.next_request().await.unwrap();
+    assert_eq!(resubscribe["event"], "subscribe");
+}
+
+#[tokio::test]
+async fn test_watchdog() {
+    let server = MockServer::start().await.unwrap();
+    let config = ws::Config {
+        ping_interval: Duration::from_millis(50),
+        silence_timeout: Duration::from_millis(300),
+        ..config(&server)
+    };
+    let mut client = connect(config).await;
+
+    // an idle connection is pinged
+    expect(&mut client, |event| matches!(event, Event::Latency(_))).await;
+    assert!(client.liveness().unwrap().latency.is_some());
+
+    // and dropped once the pings go unanswered
+    for _ in 0..10 {
+        server.script("ping", Reply::Ignore);
+    }
+    expect(&mut client, |event| matches!(event, Event::Disconnected)).await;
+    expect(&mut client, |event| matches!(event, Event::Connected)).await;
+    assert_eq!(server.connections(), 2);
+}
+
+#[tokio::test]
+async fn test_record() {
+    use kraken::ws::{Replayer, Speed};
+
+    let path = std::env::temp_dir().join(format!("kraken-record-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let server = MockServer::start().await.unwrap();
+    let client = connect(config(&server)).await;
+    client.record(&path).unwrap();
+    client.send(Ping::builder().reqid(1).build()).await.unwrap();
+    client.stop_recording();
+    client.send(Ping::builder().reqid(2).build()).await.unwrap();
+
+    let recording = std::fs::read_to_string(&path).unwrap();
+    let frames = recording
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0]["dir"], "out");
+    assert_eq!(frames[1]["dir"], "in");
+
+    let mut replayer = Replayer::load(&path, Speed::Original).unwrap();
+    match replayer.next().await {
+        Some(Event::Message(message)) => assert!(matches!(*message, Message::Response(_))),
+        event => panic!("expected the recorded pong, got {:?}", event),
+    }
+    assert!(replayer.next().await.is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_throttled_order() {
+    use kraken::message::request::CancelAll;
+    use kraken::rate_limit::{OrderAction, RateLimiter, Tier};
+
+    let mut server = MockServer::start().await.unwrap();
+    // fills the counter of XBT/USD, the next order has to wait a second for it to decay
+    let limiter = RateLimiter::new(Tier::Starter);
+    let pair: kraken::core::CurrencyPair = "XBT/USD".parse().unwrap();
+    for _ in 0..60 {
+        limiter
+            .order(&OrderAction::Add(pair.clone()))
+            .await
+            .unwrap();
+    }
+    let config = ws::Config {
+        rate_limiter: Some(limiter),
+        ..config(&server)
+    };
+    let client = connect(config).await;
+
+    let add_order = AddOrder::builder()
+        .token("token")
+        .ordertype(OrderType::Market)
+        .ty(Side::Buy)
+        .pair(pair)
+        .volume(1)
+        .build();
+    let placed = client.send(add_order);
+    let cancelled = client.send(CancelAll::builder().token("token").build());
+    let (placed, cancelled) = tokio::join!(placed, cancelled);
+    assert!(matches!(placed.unwrap().status, OrderStatus::Ok));
+    assert!(matches!(cancelled.unwrap().status, OrderStatus::Ok));
+
+    // the cancellation made after the order doesn't overtake it
+    assert_eq!(server.next_request().await.unwrap()["event"], "addOrder");
+    assert_eq!(server.next_request().await.unwrap()["event"], "cancelAll");
+}
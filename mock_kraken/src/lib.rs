@@ -0,0 +1,416 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+// How the server answers a request.
+#[derive(Debug, Clone)]
+pub enum Reply {
+    // What Kraken would answer.
+    Default,
+    // A failed status for the request's event, carrying `errorMessage`.
+    Error(String),
+    // No answer at all.
+    Ignore,
+    // These raw frames instead of an answer.
+    Frames(Vec<String>),
+}
+
+// A channel subscribed to on the current connection.
+// Private channels (`ownTrades`, `openOrders`) have no channel id or pair.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub id: Option<i64>,
+    pub name: String,
+    pub channel_name: String,
+    pub pair: Option<String>,
+    pub subscription: Value,
+}
+
+enum Outgoing {
+    Text(String),
+    Disconnect,
+}
+
+#[derive(Default)]
+struct State {
+    replies: HashMap<String, VecDeque<Reply>>,
+    // Only the latest connection is served, an older one is dropped once the client reconnects.
+    connection: Option<mpsc::UnboundedSender<Outgoing>>,
+    channels: Vec<Channel>,
+    next_channel_id: i64,
+    next_txid: u64,
+    connections: usize,
+}
+
+// A local WebSocket server speaking Kraken's protocol.
+// Requests are answered the way Kraken would unless scripted otherwise, and every request is
+// recorded so tests can assert on what the client sent.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    requests: mpsc::UnboundedReceiver<Value>,
+}
+
+impl MockServer {
+    // Must be called from within a tokio runtime.
+    pub async fn start() -> std::io::Result<Self> {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let (request_tx, requests) = mpsc::unbounded_channel();
+
+        let accept_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, accept_state.clone(), request_tx.clone()));
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            requests,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    // Answers the next request with `event` (e.g. `subscribe`) with `reply`.
+    // Replies are queued per event and consumed in order.
+    pub fn script(&self, event: &str, reply: Reply) {
+        self.state
+            .lock()
+            .unwrap()
+            .replies
+            .entry(event.to_string())
+            .or_default()
+            .push_back(reply);
+    }
+
+    // The next request received, in the order they arrived.
+    pub async fn next_request(&mut self) -> Option<Value> {
+        self.requests.recv().await
+    }
+
+    // How many connections were accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    pub fn channels(&self) -> Vec<Channel> {
+        self.state.lock().unwrap().channels.clone()
+    }
+
+    // The public channel subscribed to `name` (e.g. `book`) for `pair`.
+    pub fn channel(&self, name: &str, pair: &str) -> Option<Channel> {
+        self.state
+            .lock()
+            .unwrap()
+            .channels
+            .iter()
+            .find(|channel| channel.name == name && channel.pair.as_deref() == Some(pair))
+            .cloned()
+    }
+
+    // Sends a raw frame, which doesn't have to be valid.
+    // Returns `false` if no client is connected.
+    pub fn send(&self, text: impl Into<String>) -> bool {
+        self.outgoing(Outgoing::Text(text.into()))
+    }
+
+    // Publishes `data` on the channel subscribed to `name` for `pair`, framed the way Kraken does.
+    // Returns `false` if there is no such channel.
+    pub fn publish(&self, name: &str, pair: &str, data: Value) -> bool {
+        match self.channel(name, pair) {
+            Some(channel) => {
+                self.send(json!([channel.id, data, channel.channel_name, pair]).to_string())
+            }
+            None => false,
+        }
+    }
+
+    pub fn heartbeat(&self) -> bool {
+        self.send(json!({"event": "heartbeat"}).to_string())
+    }
+
+    // Publishes a `book` update whose checksum can't match any book.
+    pub fn corrupt_book(&self, pair: &str) -> bool {
+        self.publish(
+            "book",
+            pair,
+            json!({"a": [["0.00001", "0.00000000", "1534614335.345903"]], "c": "0"}),
+        )
+    }
+
+    // Drops the connection without a close handshake.
+    pub fn disconnect(&self) -> bool {
+        self.outgoing(Outgoing::Disconnect)
+    }
+
+    fn outgoing(&self, outgoing: Outgoing) -> bool {
+        match &self.state.lock().unwrap().connection {
+            Some(connection) => connection.send(outgoing).is_ok(),
+            None => false,
+        }
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    state: Arc<Mutex<State>>,
+    requests: mpsc::UnboundedSender<Value>,
+) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+
+    let (connection, mut outgoing) = mpsc::unbounded_channel();
+    let connection_id = {
+        let mut state = state.lock().unwrap();
+        state.connections += 1;
+        state.connection = Some(connection);
+        // channel ids are only valid for the connection that assigned them
+        state.channels.clear();
+        state.connections
+    };
+    let status = json!({
+        "connectionID": connection_id,
+        "event": "systemStatus",
+        "status": "online",
+        "version": "1.0.0",
+    });
+    if socket.send(Frame::text(status.to_string())).await.is_err() {
+        return;
+    }
+
+    loop {
+        let frames = tokio::select! {
+            frame = socket.next() => match frame {
+                Some(Ok(Frame::Text(text))) => match serde_json::from_str::<Value>(&text) {
+                    Ok(request) => {
+                        let _ = requests.send(request.clone());
+                        state.lock().unwrap().reply(&request)
+                    }
+                    Err(_) => vec![error(None, "Malformed request")],
+                },
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => return,
+            },
+            outgoing = outgoing.recv() => match outgoing {
+                Some(Outgoing::Text(text)) => vec![text],
+                Some(Outgoing::Disconnect) | None => return,
+            },
+        };
+
+        for text in frames {
+            if socket.send(Frame::text(text)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Echoes the request's `reqid`, if it has one.
+fn with_reqid(mut response: Value, request: &Value) -> String {
+    if let (Some(object), Some(reqid)) = (response.as_object_mut(), request.get("reqid")) {
+        object.insert("reqid".to_string(), reqid.clone());
+    }
+    response.to_string()
+}
+
+fn error(request: Option<&Value>, message: &str) -> String {
+    let response = json!({"event": "error", "errorMessage": message});
+    match request {
+        Some(request) => with_reqid(response, request),
+        None => response.to_string(),
+    }
+}
+
+impl State {
+    fn reply(&mut self, request: &Value) -> Vec<String> {
+        let event = request["event"].as_str().unwrap_or_default().to_string();
+        let reply = self
+            .replies
+            .get_mut(&event)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or(Reply::Default);
+
+        match reply {
+            Reply::Default => self.answer(&event, request),
+            Reply::Error(message) => Self::fail(&event, request, &message),
+            Reply::Ignore => Vec::new(),
+            Reply::Frames(frames) => frames,
+        }
+    }
+
+    fn answer(&mut self, event: &str, request: &Value) -> Vec<String> {
+        let response = match event {
+            "ping" => json!({"event": "pong"}),
+            "subscribe" => return self.subscribe(request),
+            "unsubscribe" => return self.unsubscribe(request),
+            "addOrder" => {
+                self.next_txid += 1;
+                json!({
+                    "descr": format!(
+                        "{} {} {} @ {}",
+                        request["type"].as_str().unwrap_or_default(),
+                        request["volume"].as_str().unwrap_or_default(),
+                        request["pair"].as_str().unwrap_or_default(),
+                        request["ordertype"].as_str().unwrap_or_default(),
+                    ),
+                    "event": "addOrderStatus",
+                    "status": "ok",
+                    "txid": format!("OMOCK-{:05}-TXID", self.next_txid),
+                })
+            }
+            "cancelOrder" => json!({"event": "cancelOrderStatus", "status": "ok"}),
+            "cancelAll" => json!({"count": 0, "event": "cancelAllStatus", "status": "ok"}),
+            _ => return vec![error(Some(request), "Unsupported event")],
+        };
+        vec![with_reqid(response, request)]
+    }
+
+    fn fail(event: &str, request: &Value, message: &str) -> Vec<String> {
+        let status_event = match event {
+            "subscribe" | "unsubscribe" => {
+                let subscription = subscription(request);
+                return pairs(request)
+                    .into_iter()
+                    .map(|pair| {
+                        let mut status = json!({
+                            "errorMessage": message,
+                            "event": "subscriptionStatus",
+                            "status": "error",
+                            "subscription": subscription,
+                        });
+                        if let Some(pair) = pair {
+                            status["pair"] = json!(pair);
+                        }
+                        with_reqid(status, request)
+                    })
+                    .collect();
+            }
+            "addOrder" => "addOrderStatus",
+            "cancelOrder" => "cancelOrderStatus",
+            "cancelAll" => "cancelAllStatus",
+            _ => return vec![error(Some(request), message)],
+        };
+        let response = json!({"errorMessage": message, "event": status_event, "status": "error"});
+        vec![with_reqid(response, request)]
+    }
+
+    fn subscribe(&mut self, request: &Value) -> Vec<String> {
+        let subscription = subscription(request);
+        let name = subscription["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let channel_name = match name.as_str() {
+            "book" => format!("book-{}", subscription["depth"].as_i64().unwrap_or(10)),
+            "ohlc" => format!("ohlc-{}", subscription["interval"].as_i64().unwrap_or(1)),
+            _ => name.clone(),
+        };
+
+        pairs(request)
+            .into_iter()
+            .map(|pair| {
+                let id = pair.as_ref().map(|_| {
+                    self.next_channel_id += 1;
+                    self.next_channel_id
+                });
+                let channel = Channel {
+                    id,
+                    name: name.clone(),
+                    channel_name: channel_name.clone(),
+                    pair,
+                    subscription: subscription.clone(),
+                };
+                let status = status(&channel, "subscribed");
+                self.channels.push(channel);
+                with_reqid(status, request)
+            })
+            .collect()
+    }
+
+    fn unsubscribe(&mut self, request: &Value) -> Vec<String> {
+        let removed = match request.get("channelID").and_then(Value::as_i64) {
+            Some(id) => self.remove(|channel| channel.id == Some(id)),
+            None => {
+                let subscription = subscription(request);
+                let name = subscription["name"].as_str().unwrap_or_default();
+                let pairs = pairs(request);
+                self.remove(|channel| {
+                    channel.name == name
+                        && channel.subscription.get("depth") == subscription.get("depth")
+                        && channel.subscription.get("interval") == subscription.get("interval")
+                        && pairs.contains(&channel.pair)
+                })
+            }
+        };
+
+        if removed.is_empty() {
+            return Self::fail("unsubscribe", request, "Subscription Not Found");
+        }
+        removed
+            .iter()
+            .map(|channel| with_reqid(status(channel, "unsubscribed"), request))
+            .collect()
+    }
+
+    fn remove<F: Fn(&Channel) -> bool>(&mut self, matches: F) -> Vec<Channel> {
+        let (removed, kept) = self
+            .channels
+            .drain(..)
+            .partition(|channel| matches(channel));
+        self.channels = kept;
+        removed
+    }
+}
+
+// The request's subscription without its token, as echoed in statuses.
+fn subscription(request: &Value) -> Value {
+    let mut subscription = request["subscription"].clone();
+    if let Some(object) = subscription.as_object_mut() {
+        object.remove("token");
+    }
+    subscription
+}
+
+// The request's pairs, or a single `None` for private channels.
+fn pairs(request: &Value) -> Vec<Option<String>> {
+    match request["pair"].as_array() {
+        Some(pairs) => pairs
+            .iter()
+            .map(|pair| pair.as_str().map(str::to_string))
+            .collect(),
+        None => vec![None],
+    }
+}
+
+fn status(channel: &Channel, status: &str) -> Value {
+    let mut response = json!({
+        "channelName": channel.channel_name,
+        "event": "subscriptionStatus",
+        "status": status,
+        "subscription": channel.subscription,
+    });
+    if let Some(id) = channel.id {
+        response["channelID"] = json!(id);
+    }
+    if let Some(pair) = &channel.pair {
+        response["pair"] = json!(pair);
+    }
+    response
+}